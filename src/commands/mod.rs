@@ -1,7 +1,16 @@
-use std::path::PathBuf;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use structopt::clap::{Error, ErrorKind};
 use structopt::StructOpt;
 
+use crate::joneslib::loader::BackendKind;
+use crate::joneslib::traversal::validate_glob;
+
+#[allow(clippy::upper_case_acronyms)]
 #[derive(StructOpt)]
+#[structopt(
+    usage = "jones [FLAGS] [OPTIONS] <class-name> [path]\n    jones [FLAGS] [OPTIONS] index <SUBCOMMAND>"
+)]
 pub struct CLI {
     /// Flag to search all classes with that value
     #[structopt(
@@ -11,11 +20,200 @@ pub struct CLI {
     )]
     pub grep: bool,
 
+    /// Flag to show whole method docstrings
+    #[structopt(
+        short = "d",
+        long = "docs",
+        help = "Show the full docstring of each method instead of its first line"
+    )]
+    pub docs: bool,
+
+    /// Flag to fail when files could not be parsed, for --grep only
+    #[structopt(
+        long = "strict",
+        help = "With --grep, exit with an error status when project files could not be parsed"
+    )]
+    pub strict: bool,
+
+    /// Flag to leave the on-disk index alone
+    #[structopt(
+        long = "no-index",
+        help = "Neither read nor update the on-disk index of the project"
+    )]
+    pub no_index: bool,
+
     /// Class name to be fetched
     #[structopt(help = "Name of the Python class")]
-    pub class_name: String,
+    pub class_name: Option<String>,
 
     /// Search path
     #[structopt(parse(from_os_str), default_value = ".", help = "Search directory")]
     pub path: PathBuf,
+
+    /// Parser backend
+    #[structopt(
+        long = "backend",
+        default_value = "native",
+        possible_values = &["native", "python"],
+        help = "Parse sources natively or with a Python interpreter"
+    )]
+    pub backend: BackendKind,
+
+    /// Interpreter of the Python backend
+    #[structopt(
+        long = "python",
+        parse(from_os_str),
+        help = "Python interpreter of the python backend, instead of JONES_PYTHON, a project .venv or python3"
+    )]
+    pub python: Option<OsString>,
+
+    /// Globs the scanned files must match
+    #[structopt(
+        long = "include",
+        number_of_values = 1,
+        validator = validate_glob,
+        help = "Only scan the files matching this glob, by name or by path from the search directory. Can be repeated"
+    )]
+    pub include: Vec<String>,
+
+    /// Globs of the files and directories left out
+    #[structopt(
+        long = "exclude",
+        number_of_values = 1,
+        validator = validate_glob,
+        help = "Leave out the files and directories matching this glob, by name or by path from the search directory. Can be repeated"
+    )]
+    pub exclude: Vec<String>,
+
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+}
+impl CLI {
+    /// Parses the command line. The class name is only required without a
+    /// subcommand, and arguments past the search directory, which clap takes
+    /// for an unknown subcommand, are refused.
+    pub fn parse() -> Self {
+        let cli = CLI::from_args();
+        match &cli.command {
+            None => match &cli.class_name {
+                None => Error::with_description(
+                    "The following required arguments were not provided:\n    <class-name>\n\nFor more information try --help",
+                    ErrorKind::MissingRequiredArgument,
+                )
+                .exit(),
+                Some(name) => match validate_name(name, cli.grep) {
+                    Ok(()) => cli,
+                    Err(error) => Error::with_description(&error, ErrorKind::ValueValidation).exit(),
+                },
+            },
+            Some(Command::Unexpected(arguments)) => Error::with_description(
+                &format!(
+                    "Found argument '{}' which wasn't expected, or isn't valid in this context",
+                    arguments[0].to_string_lossy()
+                ),
+                ErrorKind::UnknownArgument,
+            )
+            .exit(),
+            _ => cli,
+        }
+    }
+
+    /// The directory searched, or the one a subcommand works on.
+    pub fn project_path(&self) -> &Path {
+        match &self.command {
+            Some(Command::Index(IndexCommand::Rebuild { path }))
+            | Some(Command::Index(IndexCommand::Stats { path })) => path,
+            Some(Command::Unexpected(_)) | None => &self.path,
+        }
+    }
+}
+
+#[derive(StructOpt)]
+pub enum Command {
+    /// Manage the on-disk index of a project
+    Index(IndexCommand),
+    /// Anything else, taken by clap for a subcommand so that class names like
+    /// `Index` are not mistaken for a misspelt one
+    #[structopt(external_subcommand)]
+    Unexpected(Vec<OsString>),
+}
+
+#[derive(StructOpt)]
+pub enum IndexCommand {
+    /// Index the whole project again
+    Rebuild {
+        #[structopt(parse(from_os_str), default_value = ".", help = "Project directory")]
+        path: PathBuf,
+    },
+    /// Show what the index of the project holds
+    Stats {
+        #[structopt(parse(from_os_str), default_value = ".", help = "Project directory")]
+        path: PathBuf,
+    },
+}
+
+/// Names are Python identifiers, possibly qualified with dots. Only `--grep`
+/// takes an empty name, which matches every definition.
+fn validate_name(name: &str, grep: bool) -> Result<(), String> {
+    if (name.is_empty() && grep) || name.split('.').all(is_identifier) {
+        Ok(())
+    } else {
+        Err(format!(
+            "{:?} is not a Python name, only identifiers joined by dots are allowed",
+            name
+        ))
+    }
+}
+
+fn is_identifier(part: &str) -> bool {
+    let mut characters = part.chars();
+    characters
+        .next()
+        .is_some_and(|first| first == '_' || first.is_alphabetic())
+        && characters.all(|character| character == '_' || character.is_alphanumeric())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{validate_name, Command, IndexCommand, CLI};
+    use std::path::Path;
+    use structopt::StructOpt;
+
+    #[test]
+    fn test_validate_name() {
+        assert!(validate_name("shop.models.Order.Meta", false).is_ok());
+        assert!(validate_name("CaféMenu", false).is_ok());
+        assert!(validate_name("_private", false).is_ok());
+        assert!(validate_name("", true).is_ok());
+        assert!(validate_name("", false).is_err());
+        for name in [
+            "..",
+            "a..b",
+            ".Foo",
+            "Foo.",
+            "1abc",
+            "X\" or True or \"",
+            "Order; rm",
+        ] {
+            assert!(validate_name(name, false).is_err(), "{}", name);
+            assert!(validate_name(name, true).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_index_subcommand() {
+        let cli = CLI::from_iter(&["jones", "--backend", "python", "index", "stats", "shop"]);
+        assert!(matches!(
+            cli.command,
+            Some(Command::Index(IndexCommand::Stats { .. }))
+        ));
+        assert_eq!(cli.project_path(), Path::new("shop"));
+
+        // a class name close to the subcommand is not taken for it
+        let cli = CLI::from_iter(&["jones", "Index", "shop"]);
+        assert!(cli.command.is_none());
+        assert_eq!(cli.class_name.as_deref(), Some("Index"));
+        let cli = CLI::from_iter(&["jones", "--", "index"]);
+        assert_eq!(cli.class_name.as_deref(), Some("index"));
+    }
 }
@@ -7,36 +7,250 @@ License: MIT
 
 Copyright 2021 Vlad Nedelcu
 */
+use std::path::Path;
+
+use super::index::IndexStats;
 use super::objects;
-use super::ClassMatch;
 use ansi_term::Colour;
 
-pub fn output_class(python_class: &objects::PythonClass) {
+/// Prints the class with its members. Methods show the first line of their
+/// docstring, or all of it when `full_docstrings` is set.
+pub fn output_class(python_class: &objects::PythonClass, full_docstrings: bool) {
     println!("{}", python_class);
 
-    for method in python_class.methods.iter() {
+    let (properties, methods): (Vec<&objects::Method>, Vec<&objects::Method>) = python_class
+        .methods
+        .iter()
+        .partition(|method| method.kind().is_property());
+
+    if !python_class.nested_classes.is_empty() {
+        println!("\n# Nested classes\n-------");
+        for nested_class in python_class.nested_classes.iter() {
+            println!(
+                ":: [{}] -> {}{}",
+                Colour::Yellow.paint(&nested_class.qualified_name),
+                Colour::Green.paint(nested_class.inheritance.join(", ")),
+                objects::location_suffix(&nested_class.location)
+            );
+        }
+    }
+
+    if !python_class.members.is_empty() {
+        println!("\n# Members\n-------");
+        for member in python_class.members.iter() {
+            println!("{}", member);
+        }
+    }
+
+    if !python_class.fields.is_empty() {
+        println!("\n# Fields\n-------");
+        for field in python_class.fields.iter() {
+            println!("{}", field);
+        }
+    }
+
+    if !python_class.managers.is_empty() {
+        println!("\n# Managers\n-------");
+        for manager in python_class.managers.iter() {
+            println!(
+                ":: [{}] -> {}{}",
+                Colour::Yellow.paint(&manager.name),
+                Colour::Cyan.paint(manager.default.as_deref().unwrap_or_default()),
+                objects::location_suffix(&manager.location)
+            );
+        }
+    }
+
+    if !python_class.validators.is_empty() {
+        println!("\n# Validators\n-------");
+        for validator in python_class.validators.iter() {
+            println!("{}", validator);
+        }
+    }
+
+    if !python_class.attributes.is_empty() {
+        println!("\n# Attributes\n-------");
+        for attribute in python_class.attributes.iter() {
+            println!("{}", attribute);
+        }
+    }
+
+    if !properties.is_empty() {
+        println!("\n# Properties\n-------");
+        output_properties(&properties);
+    }
+
+    println!("\n# Methods\n-------");
+    for method in methods.iter() {
         println!("{}", method);
+        output_docstring(method, full_docstrings);
         for parameter in method.parameters.iter() {
             println!("{}", parameter);
         }
     }
 }
 
-pub fn not_found_message() -> () {
+/// Prints a module-level function with its decorators, docstring and
+/// parameters.
+pub fn output_function(function: &objects::Method, full_docstrings: bool) {
+    println!(
+        "# Function [{}]\n--------",
+        Colour::Cyan.paint(&function.name)
+    );
+    for line in decorator_lines(function) {
+        println!("{}", line);
+    }
+    println!("{}", function);
+    output_docstring(function, full_docstrings);
+    for parameter in function.parameters.iter() {
+        println!("{}", parameter);
+    }
+}
+
+/// The decorators of a function, one line each as written above it.
+fn decorator_lines(function: &objects::Method) -> Vec<String> {
+    function
+        .decorators
+        .iter()
+        .map(|decorator| Colour::Purple.paint(format!("@{}", decorator)).to_string())
+        .collect()
+}
+
+fn output_docstring(method: &objects::Method, full_docstrings: bool) {
+    let lines = match (&method.docstring, full_docstrings) {
+        (Some(docstring), true) => docstring.lines().collect::<Vec<&str>>(),
+        _ => method.docstring_summary().into_iter().collect(),
+    };
+    for line in lines {
+        println!(
+            "  {} {}",
+            Colour::Blue.paint(">"),
+            Colour::Yellow.paint(line)
+        );
+    }
+}
+
+/// Shows each property once, with the type of its getter and the accessors
+/// defined for it.
+fn output_properties(properties: &[&objects::Method]) {
+    let mut names: Vec<&str> = Vec::new();
+    for property in properties.iter() {
+        if !names.contains(&property.name.as_str()) {
+            names.push(&property.name);
+        }
+    }
+
+    for name in names {
+        let accessors = properties
+            .iter()
+            .filter(|property| property.name == name)
+            .collect::<Vec<&&objects::Method>>();
+        let getter = accessors
+            .iter()
+            .find(|property| property.kind() == objects::MethodKind::PropertyGetter);
+        let output = getter
+            .map(|getter| getter.output.as_str())
+            .unwrap_or("None");
+        let location = getter.unwrap_or(&accessors[0]).location.clone();
+        let labels = accessors
+            .iter()
+            .flat_map(|property| property.labels())
+            .collect::<Vec<&str>>();
+        println!(
+            ":: [{}] -> {} {}{}",
+            Colour::Yellow.paint(name),
+            Colour::Cyan.paint(output),
+            Colour::Blue.paint(format!("<{}>", labels.join(", "))),
+            objects::location_suffix(&location)
+        );
+    }
+}
+
+/// Lists the files a scan skipped, with the reason each could not be parsed.
+pub fn skipped_files(skipped: &[objects::SkippedFile]) {
+    if skipped.is_empty() {
+        return;
+    }
+    println!(
+        "\n> [{}] {} file(s) could not be parsed",
+        Colour::Red.paint("SKIPPED"),
+        skipped.len()
+    );
+    for skipped_file in skipped.iter() {
+        println!("{}", skipped_file);
+    }
+}
+
+/// Prints where the index of a project is stored and what it holds.
+pub fn index_stats(location: Option<&Path>, stats: &IndexStats) {
+    let location = match location {
+        Some(location) => location.display().to_string(),
+        None => "not stored".to_string(),
+    };
+    println!("> [{}] {}", Colour::Cyan.paint("INDEX"), location);
+    let counts = [
+        ("files", stats.files),
+        ("outdated files", stats.outdated),
+        ("classes", stats.classes),
+        ("functions", stats.functions),
+        ("constants", stats.constants),
+        ("skipped files", stats.skipped),
+        ("extracted definitions", stats.extracted),
+    ];
+    for (name, count) in counts.iter() {
+        println!(":: {} -> {}", Colour::Yellow.paint(*name), count);
+    }
+    println!(
+        ":: {} -> {:.1} KiB",
+        Colour::Yellow.paint("size"),
+        stats.size as f64 / 1024.0
+    );
+}
+
+pub fn not_found_message() {
     println!(
         "{}: {}",
         Colour::Green.paint("Output"),
-        Colour::Yellow.paint("Searched class was not found in project")
+        Colour::Yellow.paint("Searched class or function was not found in project")
     )
 }
 
-pub fn class_matches(found_match_classes: Vec<ClassMatch>) -> () {
+pub fn class_matches(found_matches: Vec<objects::Definition>) {
     println!("> [{}]", Colour::Cyan.paint("FOUND MATCHES"));
-    for line in found_match_classes.iter() {
+    for definition in found_matches.iter() {
         println!(
-            ":: {} -> {}",
-            Colour::Yellow.paint(&line.0.replace("\r", "")),
-            Colour::Purple.paint(&line.1)
+            ":: {} {} -> {}",
+            Colour::Blue.paint(format!("<{}>", definition.kind)),
+            Colour::Yellow.paint(definition.name.replace('\r', "")),
+            Colour::Purple.paint(&definition.file)
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::decorator_lines;
+    use crate::joneslib::native::load_python_function;
+    use ansi_term::Colour;
+
+    #[test]
+    fn test_function_decorator_lines() {
+        let test_file = std::env::temp_dir().join("jones_test_function_decorator_lines.py");
+        std::fs::write(
+            &test_file,
+            "@app.route(\"/x\")\n@functools.lru_cache(1)\ndef handler(request):\n    pass\n",
+        )
+        .unwrap();
+
+        let function = load_python_function(&test_file, "views", "handler").unwrap();
+        assert_eq!(
+            decorator_lines(&function),
+            vec![
+                Colour::Purple.paint("@app.route(\"/x\")").to_string(),
+                Colour::Purple.paint("@functools.lru_cache(1)").to_string(),
+            ]
+        );
+
+        std::fs::remove_file(&test_file).unwrap();
+    }
+}
@@ -0,0 +1,173 @@
+/*
+JonesCLI
+
+Author: Vlad Nedelcu
+Date: Jul 2021
+License: MIT
+
+Copyright 2021 Vlad Nedelcu
+*/
+use std::fs;
+use std::path::Path;
+
+use encoding_rs::{Encoding, UTF_8};
+
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+const CODING_MARKER: &str = "coding";
+const LATIN_1_LABELS: [&str; 5] = ["latin-1", "iso-8859-1", "iso-latin-1", "latin1", "l1"];
+
+/// Source text decoded from bytes, with the reason it may not be faithful.
+#[derive(Debug, PartialEq)]
+pub struct DecodedSource {
+    pub text: String,
+    pub warning: Option<String>,
+}
+
+/// Reads a source file the way Python does, honouring a BOM or a PEP 263
+/// coding declaration. Undecodable bytes get replaced and reported on
+/// stderr instead of failing the file.
+pub fn read_source(file_path: &Path) -> Result<String, String> {
+    let bytes = fs::read(file_path).map_err(|error| error.to_string())?;
+    let decoded = decode_source(&bytes);
+    if let Some(warning) = &decoded.warning {
+        eprintln!("{}: {}", file_path.display(), warning);
+    }
+    Ok(decoded.text)
+}
+
+pub fn decode_source(bytes: &[u8]) -> DecodedSource {
+    if let Some(content) = bytes.strip_prefix(UTF8_BOM) {
+        return decode_with(UTF_8, content, None);
+    }
+    let label = match declared_encoding(bytes) {
+        Some(label) => label,
+        None => return decode_with(UTF_8, bytes, None),
+    };
+    if is_latin_1(&label) {
+        // each byte is the code point of the same value, where windows-1252,
+        // which encoding_rs reads for these labels, has `€` or `“` in 0x80-0x9F
+        return DecodedSource {
+            text: bytes.iter().map(|&byte| byte as char).collect(),
+            warning: None,
+        };
+    }
+    match encoding_for(&label) {
+        Some(encoding) => decode_with(encoding, bytes, None),
+        None => decode_with(
+            UTF_8,
+            bytes,
+            Some(format!("unknown source encoding {}, read as UTF-8", label)),
+        ),
+    }
+}
+
+fn decode_with(
+    encoding: &'static Encoding,
+    bytes: &[u8],
+    warning: Option<String>,
+) -> DecodedSource {
+    match encoding.decode_without_bom_handling_and_without_replacement(bytes) {
+        Some(text) => DecodedSource {
+            text: text.into_owned(),
+            warning,
+        },
+        None => DecodedSource {
+            text: encoding.decode_without_bom_handling(bytes).0.into_owned(),
+            warning: warning.or(Some("invalid bytes replaced with U+FFFD".to_string())),
+        },
+    }
+}
+
+/// The encoding named by a PEP 263 declaration, `# -*- coding: latin-1 -*-`,
+/// which Python only looks for on the first two lines.
+fn declared_encoding(bytes: &[u8]) -> Option<String> {
+    for line in bytes.split(|byte| *byte == b'\n').take(2) {
+        let line = String::from_utf8_lossy(line);
+        let line = line.trim_start_matches([' ', '\t', '\x0c']);
+        if !line.starts_with('#') {
+            // a declaration can follow a blank line or a comment only
+            if line.trim().is_empty() {
+                continue;
+            }
+            return None;
+        }
+        let declaration = line.match_indices(CODING_MARKER).find_map(|(index, _)| {
+            let rest = &line[index + CODING_MARKER.len()..];
+            let rest = rest.strip_prefix([':', '='])?;
+            let name = rest
+                .trim_start_matches([' ', '\t'])
+                .split(|character: char| {
+                    !(character.is_ascii_alphanumeric() || "-_.".contains(character))
+                })
+                .next()
+                .unwrap_or_default();
+            (!name.is_empty()).then(|| name.to_string())
+        });
+        if declaration.is_some() {
+            return declaration;
+        }
+    }
+    None
+}
+
+/// Maps a Python codec name to an encoding, as Python normalizes them:
+/// `utf8`, `UTF-8` and `utf_8` are the same codec.
+fn encoding_for(label: &str) -> Option<&'static Encoding> {
+    let label = label.to_lowercase().replace('_', "-");
+    if label.starts_with("utf-8") || label == "utf8" {
+        return Some(UTF_8);
+    }
+    Encoding::for_label(label.as_bytes())
+}
+
+fn is_latin_1(label: &str) -> bool {
+    let label = label.to_lowercase().replace('_', "-");
+    LATIN_1_LABELS
+        .iter()
+        .any(|latin| label == *latin || label.starts_with(&format!("{}-", latin)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{declared_encoding, decode_source};
+
+    #[test]
+    fn test_declared_encoding() {
+        assert_eq!(
+            declared_encoding(b"# -*- coding: latin-1 -*-\nx = 1\n"),
+            Some("latin-1".to_string())
+        );
+        assert_eq!(
+            declared_encoding(b"#!/usr/bin/env python\n# vim: set fileencoding=cp1252 :\n"),
+            Some("cp1252".to_string())
+        );
+        assert_eq!(declared_encoding(b"x = 1\n# coding: latin-1\n"), None);
+        assert_eq!(
+            declared_encoding(b"# one\n# two\n# coding: latin-1\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_decode_source() {
+        let latin = decode_source(b"# coding: latin-1\nname = '\xe9t\xe9'\n");
+        assert_eq!(latin.text, "# coding: latin-1\nname = 'été'\n");
+        assert_eq!(latin.warning, None);
+        let control = decode_source(b"# coding: iso_8859_1\nsign = '\x80\x93'\n");
+        assert_eq!(
+            control.text,
+            "# coding: iso_8859_1\nsign = '\u{80}\u{93}'\n"
+        );
+
+        let bom = decode_source(b"\xef\xbb\xbfclass A: pass\n");
+        assert_eq!(bom.text, "class A: pass\n");
+
+        let invalid = decode_source(b"name = '\xe9'\n");
+        assert_eq!(invalid.text, "name = '\u{fffd}'\n");
+        assert!(invalid.warning.is_some());
+
+        let unknown = decode_source(b"# coding: klingon\nx = 1\n");
+        assert_eq!(unknown.text, "# coding: klingon\nx = 1\n");
+        assert!(unknown.warning.unwrap().contains("klingon"));
+    }
+}
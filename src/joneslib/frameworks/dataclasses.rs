@@ -0,0 +1,420 @@
+/*
+JonesCLI
+
+Author: Vlad Nedelcu
+Date: Jul 2021
+License: MIT
+
+Copyright 2021 Vlad Nedelcu
+*/
+use super::{
+    callee, constructor_parameter, is_true, push_field, simple_name, synthesize_constructor,
+    take_class_attributes, FACTORY_DEFAULT,
+};
+use crate::joneslib::native::syntax::{literal_value, parse_call, Call};
+use crate::joneslib::objects::{Attribute, AttributeScope, ModelKind, ParameterKind, PythonClass};
+
+const ATTRS_DECORATORS: [&str; 12] = [
+    "attr.s",
+    "attr.attrs",
+    "attr.dataclass",
+    "attr.define",
+    "attr.frozen",
+    "attr.mutable",
+    "attrs.define",
+    "attrs.frozen",
+    "attrs.mutable",
+    "define",
+    "frozen",
+    "mutable",
+];
+/// The decorators of the old attrs API, which only collect `attr.ib()` fields
+/// unless told otherwise with `auto_attribs=True`.
+const ATTRS_CLASSIC_DECORATORS: [&str; 2] = ["attr.s", "attr.attrs"];
+const ATTRS_FIELDS: [&str; 7] = [
+    "attr.ib",
+    "attr.attrib",
+    "attr.field",
+    "attrs.field",
+    "attrib",
+    "ib",
+    "field",
+];
+const NOT_REQUIRED_DEFAULT: &str = "...";
+
+pub fn model_kind(class: &PythonClass) -> Option<ModelKind> {
+    for decorator in class.decorators.iter() {
+        if ATTRS_DECORATORS.contains(&callee(decorator)) {
+            return Some(ModelKind::Attrs);
+        }
+        if simple_name(decorator) == "dataclass" {
+            return Some(ModelKind::Dataclass);
+        }
+    }
+    class
+        .inheritance
+        .iter()
+        .find_map(|base| match simple_name(base) {
+            "NamedTuple" => Some(ModelKind::NamedTuple),
+            "TypedDict" => Some(ModelKind::TypedDict),
+            _ => None,
+        })
+}
+
+/// Lists the fields of the model class and adds the `__init__` generated
+/// from them.
+pub fn apply(class: &mut PythonClass, model: ModelKind) {
+    let decorator = class
+        .decorators
+        .iter()
+        .find(|decorator| match model {
+            ModelKind::Attrs => ATTRS_DECORATORS.contains(&callee(decorator)),
+            _ => simple_name(decorator) == "dataclass",
+        })
+        .cloned();
+    let options = decorator.as_deref().and_then(parse_call);
+
+    class.model = Some(model);
+    match model {
+        ModelKind::Dataclass => dataclass_fields(class, options.as_ref()),
+        ModelKind::Attrs => {
+            let classic = decorator
+                .as_deref()
+                .is_some_and(|decorator| ATTRS_CLASSIC_DECORATORS.contains(&callee(decorator)));
+            attrs_fields(class, options.as_ref(), classic)
+        }
+        ModelKind::NamedTuple => named_tuple_fields(class),
+        ModelKind::TypedDict => typed_dict_fields(class),
+        _ => return,
+    }
+
+    let generates_init = options
+        .and_then(|options| options.keyword("init"))
+        .is_none_or(|init| init != "False");
+    if generates_init {
+        synthesize_constructor(class);
+    }
+}
+
+fn is_class_variable(attribute: &Attribute) -> bool {
+    attribute
+        .annotation
+        .as_deref()
+        .is_some_and(|annotation| simple_name(annotation) == "ClassVar")
+}
+
+fn is_annotated_field(attribute: &Attribute) -> bool {
+    attribute.annotation.is_some() && !is_class_variable(attribute)
+}
+
+fn field_kind(keyword_only: bool) -> ParameterKind {
+    if keyword_only {
+        ParameterKind::KeywordOnly
+    } else {
+        ParameterKind::Regular
+    }
+}
+
+/// Fields of a `@dataclass`: the annotated class attributes, except class
+/// variables. `field(...)` settings and the `KW_ONLY` marker are honoured.
+fn dataclass_fields(class: &mut PythonClass, options: Option<&Call>) {
+    let mut keyword_only = is_true(
+        options
+            .and_then(|options| options.keyword("kw_only"))
+            .as_deref(),
+    );
+
+    for attribute in take_class_attributes(class, is_annotated_field) {
+        let annotation = attribute.annotation.as_deref().unwrap_or_default();
+        if simple_name(annotation) == "KW_ONLY" {
+            keyword_only = true;
+            continue;
+        }
+
+        let mut default = attribute.default.clone();
+        let mut field_options = Vec::new();
+        let mut in_init = true;
+        let mut field_keyword_only = keyword_only;
+        let field_call = attribute
+            .default
+            .as_deref()
+            .and_then(parse_call)
+            .filter(|call| call.name() == "field");
+        if let Some(call) = field_call {
+            default = call.keyword("default");
+            if let Some(factory) = call.keyword("default_factory") {
+                field_options.push(format!("default_factory={}", factory));
+                default = Some(FACTORY_DEFAULT.to_string());
+            }
+            in_init = call.keyword("init").as_deref() != Some("False");
+            if let Some(value) = call.keyword("kw_only") {
+                field_keyword_only = value == "True";
+            }
+        }
+        if simple_name(annotation) == "InitVar" {
+            field_options.push("InitVar".to_string());
+        }
+
+        let parameter = in_init.then(|| {
+            constructor_parameter(
+                &attribute.name,
+                attribute.annotation.as_deref(),
+                default.as_deref(),
+                field_kind(field_keyword_only),
+                attribute.location.clone(),
+            )
+        });
+        push_field(class, attribute, default, parameter, field_options);
+    }
+}
+
+/// Fields of an attrs class. The classic `@attr.s` only collects `attr.ib()`
+/// assignments, the modern API collects the annotated attributes as well
+/// unless some field is declared without annotation.
+fn attrs_fields(class: &mut PythonClass, options: Option<&Call>, classic: bool) {
+    let field_call = |attribute: &Attribute| {
+        attribute
+            .default
+            .as_deref()
+            .and_then(parse_call)
+            .filter(|call| ATTRS_FIELDS.contains(&call.callee.as_str()))
+    };
+    let auto_attribs = match options.and_then(|options| options.keyword("auto_attribs")) {
+        Some(value) => value == "True",
+        None if classic => false,
+        None => !class.attributes.iter().any(|attribute| {
+            attribute.scope == AttributeScope::Class
+                && attribute.annotation.is_none()
+                && field_call(attribute).is_some()
+        }),
+    };
+    let keyword_only = is_true(
+        options
+            .and_then(|options| options.keyword("kw_only"))
+            .as_deref(),
+    );
+
+    let attributes = take_class_attributes(class, |attribute| {
+        if auto_attribs {
+            is_annotated_field(attribute)
+        } else {
+            field_call(attribute).is_some()
+        }
+    });
+    for attribute in attributes {
+        let mut default = attribute.default.clone();
+        let mut field_options = Vec::new();
+        let mut in_init = true;
+        let mut field_keyword_only = keyword_only;
+        let mut alias = None;
+        if let Some(call) = field_call(&attribute) {
+            default = call
+                .keyword("default")
+                .or_else(|| call.positional().into_iter().next());
+            if let Some(factory) = call.keyword("factory") {
+                default = Some(format!("Factory({})", factory));
+            }
+            in_init = call.keyword("init").as_deref() != Some("False");
+            if let Some(value) = call.keyword("kw_only") {
+                field_keyword_only = value == "True";
+            }
+            alias = call
+                .keyword("alias")
+                .and_then(|alias| literal_value(&alias));
+        }
+        let factory = default
+            .as_deref()
+            .and_then(parse_call)
+            .filter(|call| call.name() == "Factory")
+            .and_then(|call| call.positional().into_iter().next());
+        if let Some(factory) = factory {
+            field_options.push(format!("factory={}", factory));
+            default = Some(FACTORY_DEFAULT.to_string());
+        }
+        if let Some(alias) = &alias {
+            field_options.push(format!("alias={}", alias));
+        }
+
+        // attrs strips the leading underscores of private attributes from
+        // the constructor parameter names
+        let parameter_name =
+            alias.unwrap_or_else(|| attribute.name.trim_start_matches('_').to_string());
+        let parameter = in_init.then(|| {
+            constructor_parameter(
+                &parameter_name,
+                attribute.annotation.as_deref(),
+                default.as_deref(),
+                field_kind(field_keyword_only),
+                attribute.location.clone(),
+            )
+        });
+        push_field(class, attribute, default, parameter, field_options);
+    }
+}
+
+/// Fields of a `typing.NamedTuple` subclass, all positional.
+fn named_tuple_fields(class: &mut PythonClass) {
+    for attribute in take_class_attributes(class, is_annotated_field) {
+        let parameter = constructor_parameter(
+            &attribute.name,
+            attribute.annotation.as_deref(),
+            attribute.default.as_deref(),
+            ParameterKind::Regular,
+            attribute.location.clone(),
+        );
+        let default = attribute.default.clone();
+        push_field(class, attribute, default, Some(parameter), Vec::new());
+    }
+}
+
+/// Keys of a `TypedDict`, passed by keyword. Keys that may be left out,
+/// because of `total=False` or `NotRequired[...]`, show `...` as default.
+fn typed_dict_fields(class: &mut PythonClass) {
+    let total = !class
+        .keywords
+        .iter()
+        .any(|keyword| keyword.name.as_deref() == Some("total") && keyword.value == "False");
+
+    for attribute in take_class_attributes(class, is_annotated_field) {
+        let annotation = attribute.annotation.as_deref().unwrap_or_default();
+        let required = match simple_name(annotation) {
+            "Required" => true,
+            "NotRequired" => false,
+            _ => total,
+        };
+        let mut field_options = Vec::new();
+        if !required {
+            field_options.push("not required".to_string());
+        }
+        let parameter = constructor_parameter(
+            &attribute.name,
+            attribute.annotation.as_deref(),
+            (!required).then_some(NOT_REQUIRED_DEFAULT),
+            ParameterKind::KeywordOnly,
+            attribute.location.clone(),
+        );
+        push_field(class, attribute, None, Some(parameter), field_options);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::extract_model;
+    use crate::joneslib::objects::{ModelKind, PythonClass};
+
+    fn field_names(class: &PythonClass) -> Vec<&str> {
+        class
+            .fields
+            .iter()
+            .map(|field| field.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_dataclass_fields() {
+        let class = extract_model(
+            "
+@dataclass(frozen=True)
+class Point:
+    x: int
+    tags: list = field(default_factory=list)
+    cache: dict = field(default=None, init=False)
+    registry: ClassVar[dict] = {}
+    _: KW_ONLY
+    label: str = 'p'
+",
+        );
+        assert_eq!(class.model, Some(ModelKind::Dataclass));
+        assert_eq!(field_names(&class), vec!["x", "tags", "cache", "label"]);
+        assert_eq!(class.fields[1].default.as_deref(), Some("<factory>"));
+        assert_eq!(class.fields[2].options, vec!["init=False"]);
+        assert_eq!(class.attributes[0].name, "registry");
+
+        let constructor = &class.methods[0];
+        assert!(constructor.is_synthesized);
+        assert_eq!(
+            constructor.signature(),
+            "(self, x: int, tags: list = <factory>, *, label: str = 'p')"
+        );
+    }
+
+    #[test]
+    fn test_dataclass_keeps_written_constructor() {
+        let class = extract_model(
+            "
+@dataclasses.dataclass
+class Manual:
+    x: int
+
+    def __init__(self, value: str):
+        self.x = int(value)
+",
+        );
+        assert_eq!(field_names(&class), vec!["x"]);
+        assert_eq!(class.methods.len(), 1);
+        assert!(!class.methods[0].is_synthesized);
+    }
+
+    #[test]
+    fn test_attrs_fields() {
+        let class = extract_model(
+            "
+@attr.s
+class Legacy:
+    name = attr.ib()
+    _size = attr.ib(default=3, kw_only=True)
+    items = attr.ib(factory=list)
+    plain: int = 5
+",
+        );
+        assert_eq!(class.model, Some(ModelKind::Attrs));
+        assert_eq!(field_names(&class), vec!["name", "_size", "items"]);
+        assert_eq!(
+            class.methods[0].signature(),
+            "(self, name, items=<factory>, *, size=3)"
+        );
+
+        let class = extract_model(
+            "
+@define
+class Modern:
+    name: str
+    count: int = field(default=0, alias='total')
+",
+        );
+        assert_eq!(
+            class.methods[0].signature(),
+            "(self, name: str, total: int = 0)"
+        );
+    }
+
+    #[test]
+    fn test_named_tuple_and_typed_dict_fields() {
+        let class = extract_model(
+            "
+class Pair(typing.NamedTuple):
+    left: int
+    right: int = 1
+",
+        );
+        assert_eq!(class.model, Some(ModelKind::NamedTuple));
+        assert_eq!(
+            class.methods[0].signature(),
+            "(self, left: int, right: int = 1)"
+        );
+
+        let class = extract_model(
+            "
+class Movie(TypedDict):
+    title: str
+    year: NotRequired[int]
+",
+        );
+        assert_eq!(class.model, Some(ModelKind::TypedDict));
+        assert_eq!(class.fields[1].options, vec!["not required"]);
+        assert_eq!(
+            class.methods[0].signature(),
+            "(self, *, title: str, year: NotRequired[int] = ...)"
+        );
+    }
+}
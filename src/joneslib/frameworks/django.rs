@@ -0,0 +1,213 @@
+/*
+JonesCLI
+
+Author: Vlad Nedelcu
+Date: Jul 2021
+License: MIT
+
+Copyright 2021 Vlad Nedelcu
+*/
+use super::{callee, push_field, take_class_attributes};
+use crate::joneslib::native::syntax::{literal_value, parse_call, Call};
+use crate::joneslib::objects::{
+    Attribute, AttributeScope, Keyword, ModelKind, PythonClass, Relation, TypeSource,
+};
+
+/// A bare `Model` base is left out, too many other libraries have one.
+const MODEL_BASES: [&str; 2] = ["models.Model", "django.db.models.Model"];
+const MODELS_MODULE: &str = "models.";
+const RELATION_FIELDS: [&str; 3] = ["ForeignKey", "OneToOneField", "ManyToManyField"];
+/// The field options shown next to each field, the others being mostly
+/// about forms and the admin.
+const KEY_OPTIONS: [&str; 12] = [
+    "null",
+    "blank",
+    "max_length",
+    "primary_key",
+    "unique",
+    "db_index",
+    "choices",
+    "max_digits",
+    "decimal_places",
+    "auto_now",
+    "auto_now_add",
+    "on_delete",
+];
+const RELATED_OPTIONS: [&str; 2] = ["related_name", "through"];
+const META_CLASS: &str = "Meta";
+const SELF_TARGET: &str = "self";
+
+/// A Django model inherits `models.Model`, or declares `models.*` fields when
+/// it inherits an abstract model of the project.
+pub fn is_model(class: &PythonClass) -> bool {
+    class
+        .inheritance
+        .iter()
+        .any(|base| MODEL_BASES.contains(&callee(base)))
+        || class.attributes.iter().any(|attribute| {
+            field_call(attribute).is_some_and(|call| call.callee.starts_with(MODELS_MODULE))
+        })
+}
+
+fn field_call(attribute: &Attribute) -> Option<Call> {
+    if attribute.scope != AttributeScope::Class {
+        return None;
+    }
+    attribute
+        .default
+        .as_deref()
+        .and_then(parse_call)
+        .filter(|call| call.name().ends_with("Field") || RELATION_FIELDS.contains(&call.name()))
+}
+
+fn is_manager(attribute: &Attribute) -> bool {
+    attribute.scope == AttributeScope::Class
+        && attribute
+            .default
+            .as_deref()
+            .and_then(parse_call)
+            .is_some_and(|call| call.name().ends_with("Manager") || call.name() == "as_manager")
+}
+
+/// Lists the model fields with their type and key options, the relations
+/// they hold, the managers and the `Meta` options.
+pub fn apply(class: &mut PythonClass) {
+    class.model = Some(ModelKind::Django);
+    class.config = meta_options(class);
+
+    for attribute in take_class_attributes(class, |attribute| field_call(attribute).is_some()) {
+        let call = match field_call(&attribute) {
+            Some(call) => call,
+            None => continue,
+        };
+        let relation = relation(class, &call);
+        let options = call
+            .keywords()
+            .into_iter()
+            .filter(|(name, _)| {
+                KEY_OPTIONS.contains(name) || (relation.is_some() && RELATED_OPTIONS.contains(name))
+            })
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+
+        // the field class says more than a type hint on the attribute
+        let attribute = Attribute {
+            annotation: Some(call.name().to_string()),
+            type_source: TypeSource::Implementation,
+            ..attribute
+        };
+        push_field(class, attribute, call.keyword("default"), None, options);
+        if let Some(field) = class.fields.last_mut() {
+            field.relation = relation;
+            field.primary_key = call.keyword("primary_key").as_deref() == Some("True");
+        }
+    }
+    class.managers = take_class_attributes(class, is_manager);
+}
+
+/// The model a relation field points to. Django accepts the class itself, its
+/// name, `app_label.Name` or `"self"`; the app label is kept to tell apart
+/// models of different apps with the same name.
+fn relation(class: &PythonClass, call: &Call) -> Option<Relation> {
+    if !RELATION_FIELDS.contains(&call.name()) {
+        return None;
+    }
+    let target = call
+        .keyword("to")
+        .or_else(|| call.positional().into_iter().next())?;
+    let target = literal_value(&target).unwrap_or(target);
+    let target = if target == SELF_TARGET {
+        class.name.clone()
+    } else {
+        target
+    };
+    Some(Relation { target, file: None })
+}
+
+fn meta_options(class: &PythonClass) -> Vec<Keyword> {
+    class
+        .nested_classes
+        .iter()
+        .filter(|nested| nested.name == META_CLASS)
+        .flat_map(|meta| meta.attributes.iter())
+        .filter(|attribute| attribute.scope == AttributeScope::Class)
+        .map(|attribute| Keyword {
+            name: Some(attribute.name.clone()),
+            value: attribute.default.clone().unwrap_or_default(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::extract_model;
+    use crate::joneslib::objects::{ModelKind, Relation};
+
+    #[test]
+    fn test_django_model() {
+        let class = extract_model(
+            "
+class Order(models.Model):
+    number = models.CharField(max_length=20, help_text='Order number')
+    total = models.DecimalField(max_digits=10, decimal_places=2, default=0)
+    customer = models.ForeignKey('accounts.Customer', on_delete=models.CASCADE)
+    parent = models.ForeignKey(to='self', null=True, on_delete=models.SET_NULL)
+    status: str = 'new'
+
+    objects = OrderManager()
+
+    class Meta:
+        ordering = ['-number']
+",
+        );
+        assert_eq!(class.model, Some(ModelKind::Django));
+        let fields = class
+            .fields
+            .iter()
+            .map(|field| field.to_string())
+            .collect::<Vec<String>>();
+        assert!(fields[0].contains("CharField"));
+        assert_eq!(class.fields[0].options, vec!["max_length=20"]);
+        assert_eq!(class.fields[1].default.as_deref(), Some("0"));
+        assert_eq!(
+            class.fields[2].relation,
+            Some(Relation {
+                target: "accounts.Customer".to_string(),
+                file: None
+            })
+        );
+        assert_eq!(
+            class.fields[3]
+                .relation
+                .as_ref()
+                .map(|relation| relation.target.as_str()),
+            Some("Order")
+        );
+        assert_eq!(class.managers[0].name, "objects");
+        assert_eq!(class.attributes[0].name, "status");
+        assert_eq!(class.config[0].to_string(), "ordering=['-number']");
+        assert!(class.methods.is_empty());
+    }
+
+    #[test]
+    fn test_django_abstract_parent() {
+        let class = extract_model(
+            "
+class Order(TimeStamped):
+    number = models.CharField(max_length=20)
+",
+        );
+        assert_eq!(class.model, Some(ModelKind::Django));
+
+        let class = extract_model(
+            "
+class Plain(Base):
+    number = CharField(max_length=20)
+",
+        );
+        assert_eq!(class.model, None);
+
+        let class = extract_model("class Plain(Model):\n    number = 1\n");
+        assert_eq!(class.model, None);
+    }
+}
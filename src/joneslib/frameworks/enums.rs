@@ -0,0 +1,420 @@
+/*
+JonesCLI
+
+Author: Vlad Nedelcu
+Date: Jul 2021
+License: MIT
+
+Copyright 2021 Vlad Nedelcu
+*/
+use super::{simple_name, take_class_attributes};
+use crate::joneslib::native::syntax::{literal_value, parse_call, parse_dict, parse_sequence};
+use crate::joneslib::objects::{
+    Attribute, AttributeScope, Location, Member, ModelKind, PythonClass, TypeSource,
+};
+
+pub const ENUM_BASES: [&str; 6] = ["Enum", "IntEnum", "StrEnum", "Flag", "IntFlag", "ReprEnum"];
+const FLAG_BASES: [&str; 2] = ["Flag", "IntFlag"];
+const STRING_BASES: [&str; 1] = ["StrEnum"];
+const AUTO_CALL: &str = "auto";
+const NON_MEMBER_CALL: &str = "nonmember";
+const MEMBER_CALL: &str = "member";
+/// Overriding it changes what `auto()` gives, so the values are left as is.
+const NEXT_VALUE_METHOD: &str = "_generate_next_value_";
+const MISSING_DOCSTRING: &str = "None";
+
+/// How `auto()` computes the value of the next member.
+enum AutoValue {
+    /// One more than the last integer value, starting at 1.
+    Increment,
+    /// The next power of two above the previous values.
+    Flag,
+    /// The member name in lower case.
+    Name,
+    Unknown,
+}
+
+pub fn is_enum(class: &PythonClass) -> bool {
+    class
+        .inheritance
+        .iter()
+        .any(|base| ENUM_BASES.contains(&simple_name(base)))
+}
+
+/// The functional API, `Enum("Color", "RED GREEN")`, which creates an enum
+/// class without a class statement.
+pub fn is_functional_enum(expression: &str) -> bool {
+    parse_call(expression).is_some_and(|call| {
+        ENUM_BASES.contains(&call.name())
+            && call
+                .positional()
+                .first()
+                .is_some_and(|name| literal_value(name).is_some())
+    })
+}
+
+/// Builds the class made by the functional API, its members set as class
+/// attributes the way a class statement would, for `apply` to list them.
+/// Members given by name only get the values the enum would give them, or
+/// `auto()` when those are unknown.
+pub fn functional_class(
+    name: &str,
+    expression: &str,
+    location: Option<Location>,
+) -> Option<PythonClass> {
+    if !is_functional_enum(expression) {
+        return None;
+    }
+    let call = parse_call(expression)?;
+    let names = call
+        .keyword("names")
+        .or_else(|| call.positional().into_iter().nth(1))?;
+    let auto_value = if FLAG_BASES.contains(&call.name()) {
+        AutoValue::Flag
+    } else if STRING_BASES.contains(&call.name()) {
+        AutoValue::Name
+    } else {
+        AutoValue::Increment
+    };
+    let start = call.keyword("start").as_deref().and_then(integer_value);
+
+    let mut values: Vec<Option<i64>> = Vec::new();
+    let mut attributes = Vec::new();
+    for (member, value) in functional_members(&names)? {
+        let value = match (value, &auto_value) {
+            (Some(value), _) => Some(value),
+            (None, AutoValue::Name) => next_value(&auto_value, &member, &values),
+            (None, _) if values.is_empty() => Some(start.unwrap_or(1).to_string()),
+            (None, _) => next_value(&auto_value, &member, &values),
+        };
+        let value = value.unwrap_or_else(|| format!("{}()", AUTO_CALL));
+        values.push(integer_value(&value));
+        attributes.push(Attribute {
+            name: member,
+            annotation: None,
+            default: Some(value),
+            scope: AttributeScope::Class,
+            method: None,
+            location: location.clone(),
+            type_source: TypeSource::Implementation,
+        });
+    }
+
+    Some(PythonClass {
+        name: name.to_string(),
+        qualified_name: name.to_string(),
+        inheritance: vec![call.callee.clone()],
+        docstring: MISSING_DOCSTRING.to_string(),
+        attributes,
+        location,
+        ..PythonClass::default()
+    })
+}
+
+/// The members given to the functional API, with their value when it is
+/// given: `'RED GREEN'`, `'RED, GREEN'`, `['RED', 'GREEN']`,
+/// `[('RED', 1), ('GREEN', 2)]` or `{'RED': 1, 'GREEN': 2}`.
+fn functional_members(names: &str) -> Option<Vec<(String, Option<String>)>> {
+    if let Some(names) = literal_value(names) {
+        return Some(
+            names
+                .replace(',', " ")
+                .split_whitespace()
+                .map(|name| (name.to_string(), None))
+                .collect(),
+        );
+    }
+    if let Some(pairs) = parse_dict(names) {
+        return Some(
+            pairs
+                .into_iter()
+                .map(|(name, value)| (name, Some(value)))
+                .collect(),
+        );
+    }
+    parse_sequence(names)?
+        .iter()
+        .map(|element| match literal_value(element) {
+            Some(name) => Some((name, None)),
+            None => match parse_sequence(element)?.as_slice() {
+                [name, value] => Some((literal_value(name)?, Some(value.clone()))),
+                _ => None,
+            },
+        })
+        .collect()
+}
+
+/// Members are the class attributes set to a value, other than private and
+/// `_sunder_` names or values wrapped in `nonmember()`.
+fn is_member(attribute: &Attribute) -> bool {
+    attribute.default.is_some()
+        && !attribute.name.starts_with('_')
+        && attribute
+            .default
+            .as_deref()
+            .and_then(parse_call)
+            .is_none_or(|call| call.name() != NON_MEMBER_CALL)
+}
+
+/// Lists the enum members with their values, computing the `auto()` ones
+/// and marking aliases.
+pub fn apply(class: &mut PythonClass) {
+    class.model = Some(ModelKind::Enum);
+    let bases = class
+        .inheritance
+        .iter()
+        .map(|base| simple_name(base))
+        .collect::<Vec<&str>>();
+    let auto_value = if class
+        .methods
+        .iter()
+        .any(|method| method.name == NEXT_VALUE_METHOD)
+    {
+        AutoValue::Unknown
+    } else if bases.iter().any(|base| FLAG_BASES.contains(base)) {
+        AutoValue::Flag
+    } else if bases.iter().any(|base| STRING_BASES.contains(base)) {
+        AutoValue::Name
+    } else {
+        AutoValue::Increment
+    };
+
+    let mut values: Vec<Option<i64>> = Vec::new();
+    for attribute in take_class_attributes(class, is_member) {
+        let written = attribute.default.clone().unwrap_or_default();
+        let call = parse_call(&written);
+        let mut options = Vec::new();
+        let value = match call {
+            Some(call) if call.name() == AUTO_CALL && call.arguments.is_empty() => {
+                options.push(written.clone());
+                let value = next_value(&auto_value, &attribute.name, &values);
+                value.unwrap_or(written)
+            }
+            Some(call) if call.name() == MEMBER_CALL => {
+                call.positional().into_iter().next().unwrap_or(written)
+            }
+            _ => written,
+        };
+        values.push(integer_value(&value));
+
+        if let Some(original) = class.members.iter().find(|member| member.value == value) {
+            options.push(format!("alias of {}", original.name));
+        }
+        class.members.push(Member {
+            name: attribute.name,
+            value,
+            options,
+            location: attribute.location,
+        });
+    }
+}
+
+/// The value `auto()` gives the next member, unknown when it does not fit
+/// in an `i64`.
+fn next_value(auto_value: &AutoValue, name: &str, values: &[Option<i64>]) -> Option<String> {
+    match auto_value {
+        AutoValue::Increment => {
+            let last = values.iter().rev().flatten().next();
+            last.map_or(Some(1), |last| last.checked_add(1))
+                .map(|value| value.to_string())
+        }
+        AutoValue::Flag => {
+            let highest = values.iter().flatten().filter(|value| **value > 0).max();
+            highest
+                .map_or(Some(1), |highest| {
+                    1i64.checked_shl(64 - highest.leading_zeros())
+                        .filter(|value| *value > 0)
+                })
+                .map(|value| value.to_string())
+        }
+        AutoValue::Name => Some(format!("'{}'", name.to_lowercase())),
+        AutoValue::Unknown => None,
+    }
+}
+
+/// The value of an integer literal, in any base and possibly signed.
+fn integer_value(value: &str) -> Option<i64> {
+    let value = value.replace('_', "");
+    let (negative, digits) = match value.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, value.strip_prefix('+').unwrap_or(&value)),
+    };
+    let digits = digits.trim_start();
+    let radix = match digits.get(..2).map(str::to_ascii_lowercase).as_deref() {
+        Some("0x") => 16,
+        Some("0o") => 8,
+        Some("0b") => 2,
+        _ => 10,
+    };
+    let digits = if radix == 10 { digits } else { &digits[2..] };
+    // a sign belongs to the number, not the digits
+    if digits.starts_with(['-', '+']) {
+        return None;
+    }
+    let magnitude = i64::from_str_radix(digits, radix).ok()?;
+    Some(if negative { -magnitude } else { magnitude })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::extract_model;
+    use super::{functional_class, integer_value, is_functional_enum};
+    use crate::joneslib::frameworks;
+    use crate::joneslib::objects::ModelKind;
+
+    fn member_values(source: &str) -> Vec<(String, String)> {
+        extract_model(source)
+            .members
+            .into_iter()
+            .map(|member| (member.name, member.value))
+            .collect()
+    }
+
+    #[test]
+    fn test_enum_members() {
+        let class = extract_model(
+            "
+class Color(Enum):
+    RED = 1
+    GREEN = auto()
+    CRIMSON = 1
+    _ignore_ = ['x']
+    label: str
+    cache = nonmember({})
+",
+        );
+        assert_eq!(class.model, Some(ModelKind::Enum));
+        let members = class
+            .members
+            .iter()
+            .map(|member| member.to_string())
+            .collect::<Vec<String>>();
+        assert_eq!(members.len(), 3);
+        assert_eq!(class.members[1].value, "2");
+        assert_eq!(class.members[1].options, vec!["auto()"]);
+        assert_eq!(class.members[2].options, vec!["alias of RED"]);
+        assert_eq!(class.attributes.len(), 3);
+    }
+
+    #[test]
+    fn test_enum_auto_values() {
+        let flags = member_values(
+            "
+class Perm(enum.IntFlag):
+    R = auto()
+    W = auto()
+    X = 0x10
+    ALL = auto()
+",
+        );
+        let values = flags
+            .iter()
+            .map(|(_, value)| value.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(values, vec!["1", "2", "0x10", "32"]);
+
+        let names = member_values("class Mode(StrEnum):\n    FAST = auto()\n");
+        assert_eq!(names[0].1, "'fast'");
+
+        let custom = member_values(
+            "
+class Custom(Enum):
+    def _generate_next_value_(name, start, count, last_values):
+        return name
+    A = auto()
+",
+        );
+        assert_eq!(custom[0].1, "auto()");
+
+        let largest = member_values(
+            "
+class Large(IntEnum):
+    A = 0x7fff_ffff_ffff_ffff
+    B = auto()
+    C = auto()
+",
+        );
+        assert_eq!(largest[1].1, "auto()");
+        assert_eq!(largest[2].1, "auto()");
+        let high_flags =
+            member_values("class High(Flag):\n    A = 0x4000_0000_0000_0000\n    B = auto()\n");
+        assert_eq!(high_flags[1].1, "auto()");
+    }
+
+    #[test]
+    fn test_integer_value() {
+        assert_eq!(integer_value("1_000"), Some(1000));
+        assert_eq!(integer_value("0X1F"), Some(31));
+        assert_eq!(integer_value("0o17"), Some(15));
+        assert_eq!(integer_value("0B101"), Some(5));
+        assert_eq!(integer_value("-1"), Some(-1));
+        assert_eq!(integer_value("- 0x10"), Some(-16));
+        assert_eq!(integer_value("+7"), Some(7));
+        assert_eq!(integer_value("--1"), None);
+        assert_eq!(integer_value("0x-1"), None);
+        assert_eq!(integer_value("'a'"), None);
+
+        let values = member_values(
+            "
+class Level(IntEnum):
+    LOW = -1
+    MID = auto()
+    HIGH = 0o17
+    TOP = auto()
+",
+        );
+        let values = values
+            .iter()
+            .map(|(_, value)| value.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(values, vec!["-1", "0", "0o17", "16"]);
+    }
+
+    #[test]
+    fn test_functional_enum() {
+        assert!(is_functional_enum("Enum('Shape', 'SQUARE CIRCLE')"));
+        assert!(is_functional_enum("enum.IntEnum(\"Size\", ['S', 'M'])"));
+        assert!(!is_functional_enum("Enum(name, 'A B')"));
+        assert!(!is_functional_enum("dict('x')"));
+    }
+
+    #[test]
+    fn test_functional_class() {
+        let values = |expression: &str| {
+            let class = frameworks::apply(functional_class("Shade", expression, None).unwrap());
+            assert_eq!(class.model, Some(ModelKind::Enum));
+            class
+                .members
+                .into_iter()
+                .map(|member| format!("{}={}", member.name, member.value))
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(
+            values("Enum('Shade', 'DARK, LIGHT')"),
+            vec!["DARK=1", "LIGHT=2"]
+        );
+        assert_eq!(
+            values("enum.IntFlag('Shade', ['R', 'W', 'X'])"),
+            vec!["R=1", "W=2", "X=4"]
+        );
+        assert_eq!(
+            values("Enum('Shade', names=[('DARK', 'd'), ('LIGHT', 'l')])"),
+            vec!["DARK='d'", "LIGHT='l'"]
+        );
+        assert_eq!(
+            values("IntEnum('Shade', {'DARK': 10, 'DIM': 10})"),
+            vec!["DARK=10", "DIM=10"]
+        );
+        assert_eq!(
+            values("Enum('Shade', 'DARK LIGHT', start=0)"),
+            vec!["DARK=0", "LIGHT=1"]
+        );
+        assert_eq!(values("StrEnum('Shade', 'DARK')"), vec!["DARK='dark'"]);
+        assert_eq!(
+            values("Enum('Shade', 'DARK LIGHT', start=9223372036854775807)"),
+            vec!["DARK=9223372036854775807", "LIGHT=auto()"]
+        );
+        assert!(functional_class("Shade", "Enum('Shade', names)", None).is_none());
+        assert!(functional_class("Shade", "dict('Shade', 'A')", None).is_none());
+    }
+}
@@ -0,0 +1,193 @@
+/*
+JonesCLI
+
+Author: Vlad Nedelcu
+Date: Jul 2021
+License: MIT
+
+Copyright 2021 Vlad Nedelcu
+*/
+pub mod dataclasses;
+pub mod django;
+pub mod enums;
+pub mod pydantic;
+pub mod sqlalchemy;
+
+use super::objects::{
+    Attribute, AttributeScope, Field, Location, Method, ModelKind, Parameter, ParameterKind,
+    PythonClass,
+};
+
+const MISSING_VALUE: &str = "None";
+const CONSTRUCTOR: &str = "__init__";
+const RECEIVER: &str = "self";
+/// Default shown for fields built by a factory, as `inspect.signature` does.
+pub const FACTORY_DEFAULT: &str = "<factory>";
+
+/// Fills in the members a library generates for the classes declared with it.
+/// It works on the extracted class, so both parser backends share it.
+pub fn apply(mut class: PythonClass) -> PythonClass {
+    class.nested_classes = std::mem::take(&mut class.nested_classes)
+        .into_iter()
+        .map(apply)
+        .collect();
+
+    if let Some(model) = dataclasses::model_kind(&class) {
+        dataclasses::apply(&mut class, model);
+    } else if pydantic::is_model(&class) {
+        pydantic::apply(&mut class);
+    } else if django::is_model(&class) {
+        django::apply(&mut class);
+    } else if sqlalchemy::is_model(&class) {
+        sqlalchemy::apply(&mut class);
+    } else if enums::is_enum(&class) {
+        enums::apply(&mut class);
+    }
+    class
+}
+
+/// Name of a decorator, base class or annotation without its module path,
+/// call arguments or subscript: `typing.ClassVar[int]` gives `ClassVar`.
+pub fn simple_name(expression: &str) -> &str {
+    let expression = expression.trim_matches(['"', '\'']);
+    let head = expression.split(['(', '[']).next().unwrap_or(expression);
+    head.rsplit('.').next().unwrap_or(head).trim()
+}
+
+/// The dotted name called by an expression, `attr.s(auto_attribs=True)`
+/// gives `attr.s`.
+pub fn callee(expression: &str) -> &str {
+    expression.split('(').next().unwrap_or(expression).trim()
+}
+
+pub fn is_true(value: Option<&str>) -> bool {
+    value == Some("True")
+}
+
+/// Moves the attributes of the class body matching `predicate` out of the
+/// class attributes, keeping their order.
+pub fn take_class_attributes<F>(class: &mut PythonClass, predicate: F) -> Vec<Attribute>
+where
+    F: Fn(&Attribute) -> bool,
+{
+    let (taken, kept) = std::mem::take(&mut class.attributes)
+        .into_iter()
+        .partition(|attribute| attribute.scope == AttributeScope::Class && predicate(attribute));
+    class.attributes = kept;
+    taken
+}
+
+/// The parameter of a generated constructor setting a field.
+pub fn constructor_parameter(
+    name: &str,
+    annotation: Option<&str>,
+    default: Option<&str>,
+    kind: ParameterKind,
+    location: Option<Location>,
+) -> Parameter {
+    Parameter::new(
+        name.to_string(),
+        annotation.unwrap_or(MISSING_VALUE).to_string(),
+    )
+    .with_kind(kind)
+    .with_default(default.map(str::to_string))
+    .with_location(location)
+}
+
+/// Records the field declared by a class attribute, with the constructor
+/// parameter setting it.
+pub fn push_field(
+    class: &mut PythonClass,
+    attribute: Attribute,
+    default: Option<String>,
+    parameter: Option<Parameter>,
+    mut options: Vec<String>,
+) {
+    // the parameter type is the field annotation, stub or not
+    let parameter = parameter.map(|parameter| Parameter {
+        type_source: attribute.type_source,
+        ..parameter
+    });
+    if matches!(class.model, Some(ModelKind::Dataclass | ModelKind::Attrs)) {
+        match &parameter {
+            Some(parameter) if parameter.kind == ParameterKind::KeywordOnly => {
+                options.insert(0, "kw_only".to_string())
+            }
+            None => options.push("init=False".to_string()),
+            _ => {}
+        }
+    }
+    class.fields.push(Field {
+        name: attribute.name,
+        annotation: attribute.annotation,
+        default,
+        parameter,
+        options,
+        relation: None,
+        primary_key: false,
+        location: attribute.location,
+        type_source: attribute.type_source,
+    });
+}
+
+/// Adds the `__init__` taking the class fields, unless the class writes its
+/// own. Keyword-only parameters go last, as Python orders them.
+pub fn synthesize_constructor(class: &mut PythonClass) {
+    if class
+        .methods
+        .iter()
+        .any(|method| method.name == CONSTRUCTOR)
+    {
+        return;
+    }
+
+    let (keyword_only, positional): (Vec<Parameter>, Vec<Parameter>) = class
+        .fields
+        .iter()
+        .filter_map(|field| field.parameter.clone())
+        .partition(|parameter| parameter.kind == ParameterKind::KeywordOnly);
+    let mut parameters = vec![Parameter::new(
+        RECEIVER.to_string(),
+        MISSING_VALUE.to_string(),
+    )];
+    parameters.extend(positional);
+    parameters.extend(keyword_only);
+
+    let constructor = Method {
+        name: CONSTRUCTOR.to_string(),
+        parameters,
+        output: MISSING_VALUE.to_string(),
+        is_synthesized: true,
+        location: class.location.clone(),
+        ..Default::default()
+    }
+    .annotate_receiver();
+    class.methods.insert(0, constructor);
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::apply;
+    use crate::joneslib::native::{extract_class, syntax::parse_module, SourceFile};
+    use crate::joneslib::objects::PythonClass;
+    use std::path::Path;
+
+    /// Extracts the first class of the source natively and applies the
+    /// framework passes to it.
+    pub fn extract_model(source: &str) -> PythonClass {
+        let module = parse_module(source).unwrap();
+        let (qualified_name, class) = module.qualified_classes()[0].clone();
+        apply(extract_class(
+            &SourceFile::new(Path::new("models.py")),
+            class,
+            &qualified_name,
+        ))
+    }
+
+    #[test]
+    fn test_simple_name() {
+        assert_eq!(super::simple_name("typing.ClassVar[int]"), "ClassVar");
+        assert_eq!(super::simple_name("dataclass(frozen=True)"), "dataclass");
+        assert_eq!(super::simple_name("'NotRequired[int]'"), "NotRequired");
+    }
+}
@@ -0,0 +1,262 @@
+/*
+JonesCLI
+
+Author: Vlad Nedelcu
+Date: Jul 2021
+License: MIT
+
+Copyright 2021 Vlad Nedelcu
+*/
+use super::{
+    constructor_parameter, push_field, simple_name, synthesize_constructor, take_class_attributes,
+    FACTORY_DEFAULT,
+};
+use crate::joneslib::native::syntax::{literal_value, parse_call, parse_dict};
+use crate::joneslib::objects::{
+    Attribute, AttributeScope, Keyword, ModelKind, ParameterKind, PythonClass, Validator,
+};
+
+const MODEL_BASES: [&str; 4] = ["BaseModel", "BaseSettings", "RootModel", "GenericModel"];
+const FIELD_VALIDATORS: [&str; 2] = ["validator", "field_validator"];
+const MODEL_VALIDATORS: [&str; 2] = ["root_validator", "model_validator"];
+const CONFIG_CLASS: &str = "Config";
+const CONFIG_ATTRIBUTE: &str = "model_config";
+const CONFIG_CALLS: [&str; 2] = ["ConfigDict", "SettingsConfigDict"];
+/// The `Field(...)` arguments setting the default rather than constraining
+/// the value.
+const DEFAULT_ARGUMENTS: [&str; 2] = ["default", "default_factory"];
+const REQUIRED_DEFAULT: &str = "...";
+
+pub fn is_model(class: &PythonClass) -> bool {
+    class
+        .inheritance
+        .iter()
+        .any(|base| MODEL_BASES.contains(&simple_name(base)))
+}
+
+/// Lists the fields, validators and settings of a pydantic model, and adds
+/// the keyword-only `__init__` pydantic generates.
+pub fn apply(class: &mut PythonClass) {
+    class.model = Some(ModelKind::Pydantic);
+    class.config = model_config(class);
+    class.validators = validators(class);
+
+    for attribute in take_class_attributes(class, is_field) {
+        let mut default = attribute.default.clone();
+        let mut options = Vec::new();
+        let mut alias = None;
+        let field_call = attribute
+            .default
+            .as_deref()
+            .and_then(parse_call)
+            .filter(|call| call.name() == "Field");
+        if let Some(call) = field_call {
+            default = call
+                .keyword("default")
+                .or_else(|| call.positional().into_iter().next());
+            if let Some(factory) = call.keyword("default_factory") {
+                options.push(format!("default_factory={}", factory));
+                default = Some(FACTORY_DEFAULT.to_string());
+            }
+            for (name, value) in call.keywords() {
+                if !DEFAULT_ARGUMENTS.contains(&name) {
+                    options.push(format!("{}={}", name, value));
+                }
+            }
+            alias = call
+                .keyword("alias")
+                .and_then(|alias| literal_value(&alias));
+        }
+        // `...` marks a field as required, like having no default at all
+        let default = default.filter(|default| default != REQUIRED_DEFAULT);
+        for validator in class.validators.iter() {
+            let validates = validator
+                .fields
+                .iter()
+                .any(|field| field == &attribute.name || field == "*");
+            if validates {
+                options.push(format!("validated by {}", validator.method));
+            }
+        }
+
+        // pydantic models are built from keywords, by alias when there is one
+        let parameter = constructor_parameter(
+            alias.as_deref().unwrap_or(&attribute.name),
+            attribute.annotation.as_deref(),
+            default.as_deref(),
+            ParameterKind::KeywordOnly,
+            attribute.location.clone(),
+        );
+        push_field(class, attribute, default, Some(parameter), options);
+    }
+    synthesize_constructor(class);
+}
+
+/// Annotated class attributes are fields, except class variables, private
+/// attributes and the model configuration.
+fn is_field(attribute: &Attribute) -> bool {
+    attribute
+        .annotation
+        .as_deref()
+        .is_some_and(|annotation| simple_name(annotation) != "ClassVar")
+        && !attribute.name.starts_with('_')
+        && attribute.name != CONFIG_ATTRIBUTE
+}
+
+/// Settings from the inner `Config` class (pydantic v1), the `model_config`
+/// attribute and the class keywords (pydantic v2).
+fn model_config(class: &mut PythonClass) -> Vec<Keyword> {
+    let mut config = Vec::new();
+    if let Some(config_class) = class
+        .nested_classes
+        .iter()
+        .find(|nested| nested.name == CONFIG_CLASS)
+    {
+        for attribute in config_class.attributes.iter() {
+            if attribute.scope == AttributeScope::Class {
+                config.push(Keyword {
+                    name: Some(attribute.name.clone()),
+                    value: attribute.default.clone().unwrap_or_default(),
+                });
+            }
+        }
+    }
+
+    let config_attribute =
+        take_class_attributes(class, |attribute| attribute.name == CONFIG_ATTRIBUTE);
+    if let Some(value) = config_attribute
+        .first()
+        .and_then(|attribute| attribute.default.as_deref())
+    {
+        let settings = match parse_call(value) {
+            Some(call) if CONFIG_CALLS.contains(&call.name()) => call
+                .keywords()
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect(),
+            _ => parse_dict(value).unwrap_or_default(),
+        };
+        config.extend(settings.into_iter().map(|(name, value)| Keyword {
+            name: Some(name),
+            value,
+        }));
+    }
+
+    for keyword in class.keywords.iter() {
+        if let Some(name) = keyword.name.as_deref().filter(|name| *name != "metaclass") {
+            config.push(Keyword {
+                name: Some(name.to_string()),
+                value: keyword.value.clone(),
+            });
+        }
+    }
+    config
+}
+
+/// Methods decorated as field or model validators, with the names of the
+/// fields they check.
+fn validators(class: &PythonClass) -> Vec<Validator> {
+    let mut validators = Vec::new();
+    for method in class.methods.iter() {
+        for decorator in method.decorators.iter() {
+            let name = simple_name(decorator);
+            let validates_fields = FIELD_VALIDATORS.contains(&name);
+            if !validates_fields && !MODEL_VALIDATORS.contains(&name) {
+                continue;
+            }
+
+            let call = parse_call(decorator);
+            let mut options = vec![name.to_string()];
+            let mut fields = Vec::new();
+            if let Some(call) = call {
+                if validates_fields {
+                    fields = call
+                        .positional()
+                        .iter()
+                        .filter_map(|field| literal_value(field))
+                        .collect();
+                }
+                options.extend(
+                    call.keywords()
+                        .into_iter()
+                        .map(|(name, value)| format!("{}={}", name, value)),
+                );
+            }
+            validators.push(Validator {
+                method: method.name.clone(),
+                fields,
+                options,
+                location: method.location.clone(),
+            });
+        }
+    }
+    validators
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::extract_model;
+    use crate::joneslib::objects::ModelKind;
+
+    #[test]
+    fn test_pydantic_fields_and_validators() {
+        let class = extract_model(
+            "
+class User(BaseModel):
+    model_config = ConfigDict(extra='forbid')
+
+    id: int = Field(..., gt=0)
+    name: str = Field('anon', alias='userName')
+    _secret: str = 'x'
+
+    @field_validator('name', mode='before')
+    @classmethod
+    def strip(cls, value):
+        return value
+
+    @model_validator(mode='after')
+    def check(self):
+        return self
+",
+        );
+        assert_eq!(class.model, Some(ModelKind::Pydantic));
+        assert_eq!(class.config[0].to_string(), "extra='forbid'");
+        assert_eq!(class.fields[0].default, None);
+        assert_eq!(class.fields[0].options, vec!["gt=0"]);
+        assert_eq!(
+            class.fields[1].options,
+            vec!["alias='userName'", "validated by strip"]
+        );
+        assert_eq!(class.attributes[0].name, "_secret");
+
+        assert_eq!(class.validators[0].fields, vec!["name"]);
+        assert_eq!(
+            class.validators[0].options,
+            vec!["field_validator", "mode='before'"]
+        );
+        assert!(class.validators[1].fields.is_empty());
+        assert_eq!(
+            class.methods[0].signature(),
+            "(self, *, id: int, userName: str = 'anon')"
+        );
+    }
+
+    #[test]
+    fn test_pydantic_config_class() {
+        let class = extract_model(
+            "
+class Legacy(pydantic.BaseModel, frozen=True):
+    count: int = 0
+
+    class Config:
+        orm_mode = True
+",
+        );
+        let config = class
+            .config
+            .iter()
+            .map(|setting| setting.to_string())
+            .collect::<Vec<String>>();
+        assert_eq!(config, vec!["orm_mode=True", "frozen=True"]);
+    }
+}
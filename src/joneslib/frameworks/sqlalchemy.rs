@@ -0,0 +1,247 @@
+/*
+JonesCLI
+
+Author: Vlad Nedelcu
+Date: Jul 2021
+License: MIT
+
+Copyright 2021 Vlad Nedelcu
+*/
+use super::{push_field, simple_name, take_class_attributes};
+use crate::joneslib::native::syntax::{literal_value, parse_call, Call};
+use crate::joneslib::objects::{
+    Attribute, AttributeScope, Keyword, ModelKind, PythonClass, Relation,
+};
+
+const DECLARATIVE_BASES: [&str; 2] = ["DeclarativeBase", "DeclarativeBaseNoMeta"];
+const COLUMN_CALLS: [&str; 2] = ["Column", "mapped_column"];
+const RELATIONSHIP_CALLS: [&str; 2] = ["relationship", "relation"];
+const MAPPED_ANNOTATIONS: [&str; 3] = ["Mapped", "WriteOnlyMapped", "DynamicMapped"];
+const TABLE_NAME: &str = "__tablename__";
+const MAPPING_SETTINGS: [&str; 2] = ["__table_args__", "__mapper_args__"];
+/// The column and relationship options shown next to each field.
+const KEY_OPTIONS: [&str; 11] = [
+    "nullable",
+    "unique",
+    "index",
+    "server_default",
+    "autoincrement",
+    "back_populates",
+    "backref",
+    "secondary",
+    "lazy",
+    "uselist",
+    "cascade",
+];
+/// Names wrapping the related class in a relationship annotation, like
+/// `Mapped[Optional["User"]]`.
+const WRAPPER_NAMES: [&str; 9] = [
+    "Mapped",
+    "WriteOnlyMapped",
+    "DynamicMapped",
+    "list",
+    "List",
+    "set",
+    "Set",
+    "Optional",
+    "None",
+];
+
+/// A declarative model names its table or maps columns and relationships.
+/// The declarative base itself derives from `DeclarativeBase`.
+pub fn is_model(class: &PythonClass) -> bool {
+    class
+        .inheritance
+        .iter()
+        .any(|base| DECLARATIVE_BASES.contains(&simple_name(base)))
+        || class.attributes.iter().any(|attribute| {
+            attribute.scope == AttributeScope::Class
+                && (attribute.name == TABLE_NAME
+                    || mapping_call(attribute).is_some()
+                    || is_mapped(attribute))
+        })
+}
+
+fn mapping_call(attribute: &Attribute) -> Option<Call> {
+    attribute
+        .default
+        .as_deref()
+        .and_then(parse_call)
+        .filter(|call| {
+            COLUMN_CALLS.contains(&call.name()) || RELATIONSHIP_CALLS.contains(&call.name())
+        })
+}
+
+fn is_mapped(attribute: &Attribute) -> bool {
+    attribute
+        .annotation
+        .as_deref()
+        .is_some_and(|annotation| MAPPED_ANNOTATIONS.contains(&simple_name(annotation)))
+}
+
+/// Lists the columns and relationships of the model, with its table name and
+/// mapping settings.
+pub fn apply(class: &mut PythonClass) {
+    class.model = Some(ModelKind::SqlAlchemy);
+    class.table = take_class_attributes(class, |attribute| attribute.name == TABLE_NAME)
+        .into_iter()
+        .find_map(|attribute| attribute.default)
+        .map(|table| literal_value(&table).unwrap_or(table));
+    class.config = take_class_attributes(class, |attribute| {
+        MAPPING_SETTINGS.contains(&attribute.name.as_str())
+    })
+    .into_iter()
+    .map(|attribute| Keyword {
+        name: Some(attribute.name),
+        value: attribute.default.unwrap_or_default(),
+    })
+    .collect();
+
+    let attributes = take_class_attributes(class, |attribute| {
+        mapping_call(attribute).is_some() || is_mapped(attribute)
+    });
+    for attribute in attributes {
+        let call = mapping_call(&attribute);
+        let mut options = Vec::new();
+        let mut column_type = None;
+        let mut relation = None;
+        let mut default = None;
+        let mut primary_key = false;
+
+        if let Some(call) = &call {
+            if RELATIONSHIP_CALLS.contains(&call.name()) {
+                let target = call
+                    .positional()
+                    .into_iter()
+                    .next()
+                    .or_else(|| attribute.annotation.as_deref().and_then(annotation_target));
+                relation = target.map(|target| Relation {
+                    target: literal_value(&target).unwrap_or(target),
+                    file: None,
+                });
+                column_type = Some(call.name().to_string());
+            } else {
+                for (index, argument) in call.positional().into_iter().enumerate() {
+                    let is_foreign_key = parse_call(&argument)
+                        .is_some_and(|argument| argument.name() == "ForeignKey");
+                    if index == 0 && literal_value(&argument).is_some() {
+                        options.push(format!("name={}", argument));
+                    } else if is_foreign_key {
+                        options.push(argument);
+                    } else if column_type.is_none() {
+                        column_type = Some(argument);
+                    }
+                }
+                default = call.keyword("default");
+                primary_key = call.keyword("primary_key").as_deref() == Some("True");
+                if primary_key {
+                    options.push("primary_key=True".to_string());
+                }
+            }
+            options.extend(
+                call.keywords()
+                    .into_iter()
+                    .filter(|(name, _)| KEY_OPTIONS.contains(name))
+                    .map(|(name, value)| format!("{}={}", name, value)),
+            );
+        }
+
+        // the `Mapped[...]` annotation is the Python type, the column type
+        // then goes with the options
+        let annotation = match (&attribute.annotation, column_type) {
+            (Some(annotation), Some(column_type)) => {
+                options.insert(0, column_type);
+                Some(annotation.clone())
+            }
+            (annotation, column_type) => annotation.clone().or(column_type),
+        };
+        let attribute = Attribute {
+            annotation,
+            ..attribute
+        };
+        push_field(class, attribute, default, None, options);
+        if let Some(field) = class.fields.last_mut() {
+            field.relation = relation;
+            field.primary_key = primary_key;
+        }
+    }
+}
+
+/// The related class named by a relationship annotation:
+/// `Mapped[list["Address"]]` gives `Address`.
+fn annotation_target(annotation: &str) -> Option<String> {
+    annotation
+        .split(|character: char| {
+            !(character.is_alphanumeric() || character == '_' || character == '.')
+        })
+        .map(|name| name.rsplit('.').next().unwrap_or(name))
+        .find(|name| !name.is_empty() && !WRAPPER_NAMES.contains(name))
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::extract_model;
+    use super::annotation_target;
+    use crate::joneslib::objects::ModelKind;
+
+    #[test]
+    fn test_sqlalchemy_declarative_model() {
+        let class = extract_model(
+            "
+class User(Base):
+    __tablename__ = 'users'
+    __table_args__ = {'schema': 'auth'}
+
+    id = Column(Integer, primary_key=True)
+    email = Column('mail', String(120), unique=True, nullable=False)
+    team_id: Mapped[int] = mapped_column(ForeignKey('teams.id'))
+    name: Mapped[str]
+    addresses: Mapped[list['Address']] = relationship(back_populates='user')
+    team = relationship('Team')
+    cache = {}
+",
+        );
+        assert_eq!(class.model, Some(ModelKind::SqlAlchemy));
+        assert_eq!(class.table.as_deref(), Some("users"));
+        assert_eq!(
+            class.config[0].to_string(),
+            "__table_args__={'schema': 'auth'}"
+        );
+
+        let names = class
+            .fields
+            .iter()
+            .map(|field| field.name.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(
+            names,
+            vec!["id", "email", "team_id", "name", "addresses", "team"]
+        );
+        assert!(class.fields[0].primary_key);
+        assert_eq!(class.fields[0].annotation.as_deref(), Some("Integer"));
+        assert_eq!(
+            class.fields[1].options,
+            vec!["name='mail'", "unique=True", "nullable=False"]
+        );
+        assert_eq!(class.fields[2].annotation.as_deref(), Some("Mapped[int]"));
+        assert_eq!(class.fields[2].options, vec!["ForeignKey('teams.id')"]);
+        let targets = class
+            .fields
+            .iter()
+            .filter_map(|field| field.relation.as_ref())
+            .map(|relation| relation.target.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(targets, vec!["Address", "Team"]);
+        assert_eq!(class.attributes[0].name, "cache");
+    }
+
+    #[test]
+    fn test_annotation_target() {
+        assert_eq!(
+            annotation_target("Mapped[Optional['models.User']]"),
+            Some("User".to_string())
+        );
+        assert_eq!(annotation_target("Mapped[list]"), None);
+    }
+}
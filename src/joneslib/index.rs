@@ -0,0 +1,507 @@
+/*
+JonesCLI
+
+Author: Vlad Nedelcu
+Date: Jul 2021
+License: MIT
+
+Copyright 2021 Vlad Nedelcu
+*/
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::SystemTime;
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::frameworks::simple_name;
+use super::loader::BackendKind;
+use super::objects::{Definition, DefinitionKind, Method, ProjectScan, PythonClass, SkippedFile};
+use super::parser::SCHEMA_VERSION;
+
+const CACHE_DIR: &str = "jones";
+/// Length of the project keys naming the index directories.
+const PROJECT_KEY_LENGTH: usize = 16;
+
+/// What was found in a file, along with what identifies its content.
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct FileEntry {
+    modified: Option<SystemTime>,
+    size: u64,
+    hash: String,
+    /// The definitions of the file, once a project scan went through it.
+    #[serde(default)]
+    scan: Option<FileScan>,
+    /// The classes and functions extracted from the file, by the module path
+    /// and the name they were looked up with.
+    #[serde(default)]
+    classes: BTreeMap<String, Option<PythonClass>>,
+    #[serde(default)]
+    functions: BTreeMap<String, Option<Method>>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+struct FileScan {
+    definitions: Vec<Definition>,
+    skipped: Option<SkippedFile>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IndexDocument {
+    /// The jones version and schema which wrote the index. Another one
+    /// starts over, what it extracts may differ.
+    version: String,
+    schema: u32,
+    files: BTreeMap<String, FileEntry>,
+}
+
+/// The index document as written, borrowing the entries.
+#[derive(Serialize)]
+struct IndexDocumentRef<'a> {
+    version: &'a str,
+    schema: u32,
+    files: &'a BTreeMap<String, FileEntry>,
+}
+
+/// Counts of what an index holds.
+#[derive(Debug, Default, PartialEq)]
+pub struct IndexStats {
+    pub files: usize,
+    /// Files which changed, or are gone, since they were indexed.
+    pub outdated: usize,
+    pub classes: usize,
+    pub functions: usize,
+    pub constants: usize,
+    pub skipped: usize,
+    /// Classes and functions stored with their extracted details.
+    pub extracted: usize,
+    /// Size of the index on disk, in bytes.
+    pub size: u64,
+}
+
+/// The definitions of a project and the classes extracted from its files,
+/// kept on disk between runs. A file is looked at again when its modification
+/// time or size changed, and parsed again only when its content hash did.
+#[derive(Debug)]
+pub struct ProjectIndex {
+    /// Where the index gets stored, none when it is kept in memory only.
+    location: Option<PathBuf>,
+    files: Mutex<BTreeMap<String, FileEntry>>,
+    /// Files already checked against the disk during this run.
+    checked: Mutex<HashSet<String>>,
+    changed: AtomicBool,
+}
+impl ProjectIndex {
+    /// Opens the index of the project searched at `root` with a backend,
+    /// kept under `$XDG_CACHE_HOME/jones`. Without a cache directory the
+    /// index only lasts for the run.
+    pub fn open(backend: BackendKind, root: &Path) -> Self {
+        match index_location(backend, root) {
+            Some(location) => ProjectIndex::at(location),
+            None => ProjectIndex::disabled(),
+        }
+    }
+
+    /// Opens the index stored in a given file, starting over when it cannot
+    /// be read or was written by another jones version.
+    pub fn at(location: PathBuf) -> Self {
+        let files = fs::read(&location)
+            .ok()
+            .and_then(|content| serde_json::from_slice::<IndexDocument>(&content).ok())
+            .filter(|document| {
+                document.version == env!("CARGO_PKG_VERSION") && document.schema == SCHEMA_VERSION
+            })
+            .map(|document| document.files)
+            .unwrap_or_default();
+        ProjectIndex {
+            location: Some(location),
+            ..ProjectIndex::new(files)
+        }
+    }
+
+    /// An index which is neither read from nor written to the disk.
+    pub fn disabled() -> Self {
+        ProjectIndex::new(BTreeMap::new())
+    }
+
+    fn new(files: BTreeMap<String, FileEntry>) -> Self {
+        ProjectIndex {
+            location: None,
+            files: Mutex::new(files),
+            checked: Mutex::new(HashSet::new()),
+            changed: AtomicBool::new(false),
+        }
+    }
+
+    pub fn location(&self) -> Option<&Path> {
+        self.location.as_deref()
+    }
+
+    /// Forgets everything indexed, for the project to be indexed again.
+    pub fn clear(&self) {
+        lock(&self.files).clear();
+        lock(&self.checked).clear();
+        self.changed.store(true, Ordering::Relaxed);
+    }
+
+    /// Scans the files of a project, loading only the files which are not
+    /// indexed, or changed since, with `load`. The definitions keep the order
+    /// of the files, and the files no longer in the project are forgotten.
+    pub fn scan(
+        &self,
+        files: &[PathBuf],
+        load: impl FnOnce(&[PathBuf]) -> Option<ProjectScan>,
+    ) -> Option<ProjectScan> {
+        let keys = files
+            .par_iter()
+            .map(|file_path| self.refresh(file_path))
+            .collect::<Vec<String>>();
+        self.prune(&keys);
+        let mut scans = {
+            let indexed = lock(&self.files);
+            keys.iter()
+                .map(|key| indexed.get(key).and_then(|entry| entry.scan.clone()))
+                .collect::<Vec<Option<FileScan>>>()
+        };
+
+        let mut unknown = ProjectScan::default();
+        let stale_files = files
+            .iter()
+            .zip(scans.iter())
+            .filter(|(_, scan)| scan.is_none())
+            .map(|(file_path, _)| file_path.clone())
+            .collect::<Vec<PathBuf>>();
+        if !stale_files.is_empty() {
+            let loaded = load(&stale_files)?;
+            let mut loaded_scans: HashMap<String, FileScan> = stale_files
+                .iter()
+                .map(|file_path| (file_key(file_path), FileScan::default()))
+                .collect();
+            for definition in loaded.definitions {
+                match loaded_scans.get_mut(&definition.file) {
+                    Some(scan) => scan.definitions.push(definition),
+                    None => unknown.definitions.push(definition),
+                }
+            }
+            for skipped in loaded.skipped {
+                match loaded_scans.get_mut(&skipped.file) {
+                    Some(scan) => scan.skipped = Some(skipped),
+                    None => unknown.skipped.push(skipped),
+                }
+            }
+
+            let mut indexed = lock(&self.files);
+            for (key, scan) in keys.iter().zip(scans.iter_mut()) {
+                if scan.is_some() {
+                    continue;
+                }
+                let loaded_scan = loaded_scans.remove(key).unwrap_or_default();
+                if let Some(entry) = indexed.get_mut(key) {
+                    entry.scan = Some(loaded_scan.clone());
+                }
+                *scan = Some(loaded_scan);
+            }
+            self.changed.store(true, Ordering::Relaxed);
+        }
+
+        let mut project_scan = ProjectScan::default();
+        for scan in scans.into_iter().flatten() {
+            project_scan.definitions.extend(scan.definitions);
+            project_scan.skipped.extend(scan.skipped);
+        }
+        project_scan.definitions.extend(unknown.definitions);
+        project_scan.skipped.extend(unknown.skipped);
+        Some(project_scan)
+    }
+
+    /// Drops the entries of the files which are not among the given ones.
+    fn prune(&self, keys: &[String]) {
+        let current = keys.iter().collect::<HashSet<&String>>();
+        let mut indexed = lock(&self.files);
+        let count = indexed.len();
+        indexed.retain(|key, _| current.contains(key));
+        if indexed.len() != count {
+            self.changed.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Whether a file defines a class with the given simple name, when the
+    /// file is indexed and unchanged.
+    pub fn defines_class(&self, file_path: &Path, simple: &str) -> Option<bool> {
+        let key = self.refresh(file_path);
+        let indexed = lock(&self.files);
+        let scan = indexed.get(&key)?.scan.as_ref()?;
+        Some(scan.definitions.iter().any(|definition| {
+            definition.kind == DefinitionKind::Class && simple_name(&definition.name) == simple
+        }))
+    }
+
+    /// The class extracted from a file for a module path and a name, extracted
+    /// with `load` unless it is indexed.
+    pub fn class(
+        &self,
+        file_path: &Path,
+        module_path: &str,
+        name: &str,
+        load: impl FnOnce() -> Option<PythonClass>,
+    ) -> Option<PythonClass> {
+        let key = self.refresh(file_path);
+        let lookup = lookup_key(module_path, name);
+        let indexed = lock(&self.files)
+            .get(&key)
+            .and_then(|entry| entry.classes.get(&lookup).cloned());
+        if let Some(python_class) = indexed {
+            return python_class;
+        }
+
+        let python_class = load();
+        if let Some(entry) = lock(&self.files).get_mut(&key) {
+            entry.classes.insert(lookup, python_class.clone());
+            self.changed.store(true, Ordering::Relaxed);
+        }
+        python_class
+    }
+
+    /// The top-level function extracted from a file, like `class`.
+    pub fn function(
+        &self,
+        file_path: &Path,
+        module_path: &str,
+        name: &str,
+        load: impl FnOnce() -> Option<Method>,
+    ) -> Option<Method> {
+        let key = self.refresh(file_path);
+        let lookup = lookup_key(module_path, name);
+        let indexed = lock(&self.files)
+            .get(&key)
+            .and_then(|entry| entry.functions.get(&lookup).cloned());
+        if let Some(function) = indexed {
+            return function;
+        }
+
+        let function = load();
+        if let Some(entry) = lock(&self.files).get_mut(&key) {
+            entry.functions.insert(lookup, function.clone());
+            self.changed.store(true, Ordering::Relaxed);
+        }
+        function
+    }
+
+    /// Makes sure the entry of a file matches its content, dropping what was
+    /// found in it when the content changed. Returns the key of the entry.
+    fn refresh(&self, file_path: &Path) -> String {
+        let key = file_key(file_path);
+        if lock(&self.checked).contains(&key) {
+            return key;
+        }
+        let (modified, size) = file_stamp(file_path);
+        let unchanged = lock(&self.files).get(&key).is_some_and(|entry| {
+            modified.is_some() && entry.modified == modified && entry.size == size
+        });
+
+        if !unchanged {
+            let hash = file_hash(file_path).unwrap_or_default();
+            let mut indexed = lock(&self.files);
+            match indexed.get_mut(&key) {
+                Some(entry) if !hash.is_empty() && entry.hash == hash => {
+                    entry.modified = modified;
+                    entry.size = size;
+                }
+                _ => {
+                    indexed.insert(
+                        key.clone(),
+                        FileEntry {
+                            modified,
+                            size,
+                            hash,
+                            ..FileEntry::default()
+                        },
+                    );
+                }
+            }
+            self.changed.store(true, Ordering::Relaxed);
+        }
+        lock(&self.checked).insert(key.clone());
+        key
+    }
+
+    /// Writes the index, when it is kept on disk and anything changed. The
+    /// file gets replaced at once, so that a run never reads half of it.
+    pub fn save(&self) -> io::Result<()> {
+        let location = match &self.location {
+            Some(location) if self.changed.load(Ordering::Relaxed) => location,
+            _ => return Ok(()),
+        };
+        let content = serde_json::to_vec(&IndexDocumentRef {
+            version: env!("CARGO_PKG_VERSION"),
+            schema: SCHEMA_VERSION,
+            files: &lock(&self.files),
+        })?;
+        if let Some(dir) = location.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let partial = location.with_extension("json.partial");
+        fs::write(&partial, content)?;
+        fs::rename(&partial, location)
+    }
+
+    pub fn stats(&self) -> IndexStats {
+        let indexed = lock(&self.files);
+        let mut stats = IndexStats {
+            files: indexed.len(),
+            size: self
+                .location
+                .as_ref()
+                .and_then(|location| fs::metadata(location).ok())
+                .map(|metadata| metadata.len())
+                .unwrap_or_default(),
+            ..IndexStats::default()
+        };
+        for (key, entry) in indexed.iter() {
+            let (modified, size) = file_stamp(Path::new(key));
+            if modified.is_none() || entry.modified != modified || entry.size != size {
+                stats.outdated += 1;
+            }
+            if let Some(scan) = &entry.scan {
+                for definition in scan.definitions.iter() {
+                    match definition.kind {
+                        DefinitionKind::Class => stats.classes += 1,
+                        DefinitionKind::Function => stats.functions += 1,
+                        DefinitionKind::Constant => stats.constants += 1,
+                    }
+                }
+                stats.skipped += scan.skipped.iter().count();
+            }
+            stats.extracted += entry.classes.values().flatten().count();
+            stats.extracted += entry.functions.values().flatten().count();
+        }
+        stats
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Files are indexed under the path they are reported with.
+fn file_key(file_path: &Path) -> String {
+    file_path.display().to_string()
+}
+
+fn lookup_key(module_path: &str, name: &str) -> String {
+    format!("{}:{}", module_path, name)
+}
+
+fn file_stamp(file_path: &Path) -> (Option<SystemTime>, u64) {
+    match fs::metadata(file_path) {
+        Ok(metadata) => (metadata.modified().ok(), metadata.len()),
+        Err(_) => (None, 0),
+    }
+}
+
+fn file_hash(file_path: &Path) -> Option<String> {
+    let content = fs::read(file_path).ok()?;
+    Some(format!("{:x}", Sha256::digest(&content)))
+}
+
+/// `$XDG_CACHE_HOME/jones/<project>/<backend>.json`, falling back on the
+/// local application data on Windows and on `~/.cache` elsewhere. Projects
+/// are told apart by their directory and the path they are searched with,
+/// which is how their files get reported.
+fn index_location(backend: BackendKind, root: &Path) -> Option<PathBuf> {
+    let cache_dir = ["XDG_CACHE_HOME", "LOCALAPPDATA"]
+        .iter()
+        .filter_map(env::var_os)
+        .find(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    let canonical = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    let project = format!("{}\0{}", canonical.display(), root.display());
+    let project_key = format!("{:x}", Sha256::digest(project.as_bytes()));
+    Some(
+        cache_dir
+            .join(CACHE_DIR)
+            .join(&project_key[..PROJECT_KEY_LENGTH])
+            .join(format!("{}.json", backend.name())),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ProjectIndex;
+    use crate::joneslib::native;
+    use crate::joneslib::objects::ProjectScan;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_index_reuses_unchanged_files() {
+        let dir = std::env::temp_dir().join("jones_test_index_reuses_unchanged_files");
+        fs::create_dir_all(&dir).unwrap();
+        let files = vec![dir.join("a.py"), dir.join("b.py")];
+        fs::write(&files[0], "class A:\n    pass\n").unwrap();
+        fs::write(&files[1], "class B:\n    pass\n").unwrap();
+        let location = dir.join("index").join("native.json");
+
+        let loaded = AtomicUsize::new(0);
+        let scan = |index: &ProjectIndex| {
+            let load = |stale: &[PathBuf]| {
+                loaded.fetch_add(stale.len(), Ordering::Relaxed);
+                native::load_python_project(stale)
+            };
+            index
+                .scan(&files, load)
+                .map(|scan: ProjectScan| {
+                    scan.definitions
+                        .into_iter()
+                        .map(|definition| definition.name)
+                        .collect::<Vec<String>>()
+                })
+                .unwrap()
+        };
+
+        let index = ProjectIndex::at(location.clone());
+        assert_eq!(scan(&index), vec!["A", "B"]);
+        let class = index.class(&files[0], "a", "A", || {
+            native::load_python_object(&files[0], "a", "A")
+        });
+        assert!(class.is_some());
+        index.save().unwrap();
+        assert_eq!(loaded.load(Ordering::Relaxed), 2);
+
+        // unchanged files come from the index
+        let index = ProjectIndex::at(location.clone());
+        assert_eq!(scan(&index), vec!["A", "B"]);
+        let indexed = index.class(&files[0], "a", "A", || unreachable!("extracted again"));
+        assert_eq!(indexed, class);
+        assert_eq!(loaded.load(Ordering::Relaxed), 2);
+        let stats = index.stats();
+        assert_eq!((stats.files, stats.classes, stats.extracted), (2, 2, 1));
+
+        // rewriting a file with the same content keeps it, changing it does not
+        fs::write(&files[0], "class A:\n    pass\n").unwrap();
+        fs::write(&files[1], "class Changed:\n    pass\n").unwrap();
+        let index = ProjectIndex::at(location.clone());
+        assert_eq!(scan(&index), vec!["A", "Changed"]);
+        assert_eq!(loaded.load(Ordering::Relaxed), 3);
+        index.save().unwrap();
+
+        // removed files are forgotten
+        fs::remove_file(&files[1]).unwrap();
+        let index = ProjectIndex::at(location);
+        let remaining = index.scan(&files[..1], native::load_python_project);
+        assert_eq!(remaining.unwrap().definitions.len(), 1);
+        assert_eq!(index.stats().files, 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,224 @@
+/*
+JonesCLI
+
+Author: Vlad Nedelcu
+Date: Jul 2021
+License: MIT
+
+Copyright 2021 Vlad Nedelcu
+*/
+use std::env;
+use std::ffi::OsString;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Environment variable naming the interpreter, when `--python` is not given.
+pub const PYTHON_VARIABLE: &str = "JONES_PYTHON";
+/// Interpreters looked up on the `PATH`, in order, as a last resort.
+const FALLBACK_COMMANDS: [&str; 2] = ["python3", "python"];
+/// Virtual environment directories looked for in the project and its parents.
+const VENV_DIRS: [&str; 2] = [".venv", "venv"];
+/// Directories marking the root of a repository, above which no virtual
+/// environment is looked for.
+const REPOSITORY_DIRS: [&str; 3] = [".git", ".hg", ".svn"];
+
+#[derive(Debug, PartialEq)]
+pub enum InterpreterError {
+    /// The interpreter given with `--python` or `JONES_PYTHON` does not exist.
+    NotFound { python: OsString, origin: String },
+    /// No interpreter was given and none could be found.
+    NoneFound,
+}
+impl fmt::Display for InterpreterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InterpreterError::NotFound { python, origin } => write!(
+                f,
+                "Python interpreter {:?} given by {} was not found",
+                python, origin
+            ),
+            InterpreterError::NoneFound => write!(
+                f,
+                "No Python interpreter found: no virtual environment in the project and no {} on the PATH. Use --python or {} to choose one, or --backend native",
+                FALLBACK_COMMANDS.join(" or "),
+                PYTHON_VARIABLE
+            ),
+        }
+    }
+}
+
+/// Picks the interpreter the Python backend runs: the `--python` flag, then
+/// the `JONES_PYTHON` variable, then a `.venv` or `venv` in the project or
+/// one of its parents within its repository, then `python3` or `python` from
+/// the `PATH`.
+pub fn find_python(
+    flag: Option<OsString>,
+    variable: Option<OsString>,
+    project_path: &Path,
+) -> Result<PathBuf, InterpreterError> {
+    let explicit = match (flag, variable) {
+        (Some(python), _) => Some((python, "--python".to_string())),
+        (None, Some(python)) if !python.is_empty() => Some((python, PYTHON_VARIABLE.to_string())),
+        _ => None,
+    };
+    if let Some((python, origin)) = explicit {
+        return resolve_command(&python).ok_or(InterpreterError::NotFound { python, origin });
+    }
+
+    if let Some(python) = find_venv_python(project_path) {
+        return Ok(python);
+    }
+    FALLBACK_COMMANDS
+        .iter()
+        .find_map(|command| find_in_path(command.as_ref()))
+        .ok_or(InterpreterError::NoneFound)
+}
+
+/// A path to an executable, or a command to look up on the `PATH`.
+fn resolve_command(python: &OsString) -> Option<PathBuf> {
+    let path = Path::new(python);
+    if path.components().count() > 1 {
+        return is_executable(path).then(|| path.to_path_buf());
+    }
+    find_in_path(path)
+}
+
+fn find_in_path(command: &Path) -> Option<PathBuf> {
+    let paths = env::var_os("PATH")?;
+    env::split_paths(&paths)
+        .map(|dir| dir.join(executable(command)))
+        .find(|candidate| is_executable(candidate))
+}
+
+/// The interpreter of a virtual environment in the project, or in one of
+/// its parents up to the root of its repository. Outside of a repository
+/// only the project directory itself is looked at.
+fn find_venv_python(project_path: &Path) -> Option<PathBuf> {
+    let project_path = project_path
+        .canonicalize()
+        .unwrap_or_else(|_| project_path.to_path_buf());
+    let repository = project_path.ancestors().find(|dir| {
+        REPOSITORY_DIRS
+            .iter()
+            .any(|repository_dir| dir.join(repository_dir).exists())
+    });
+    let dirs = match repository {
+        Some(repository) => project_path
+            .ancestors()
+            .take_while(|dir| dir.starts_with(repository))
+            .collect::<Vec<&Path>>(),
+        None => vec![project_path.as_path()],
+    };
+    dirs.into_iter().find_map(|dir| {
+        VENV_DIRS
+            .iter()
+            .map(|venv| venv_python(&dir.join(venv)))
+            .find(|python| is_executable(python))
+    })
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(windows)]
+fn venv_python(venv: &Path) -> PathBuf {
+    venv.join("Scripts").join("python.exe")
+}
+
+#[cfg(not(windows))]
+fn venv_python(venv: &Path) -> PathBuf {
+    venv.join("bin").join("python")
+}
+
+#[cfg(windows)]
+fn executable(command: &Path) -> PathBuf {
+    command.with_extension("exe")
+}
+
+#[cfg(not(windows))]
+fn executable(command: &Path) -> PathBuf {
+    command.to_path_buf()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_python, find_venv_python, venv_python, InterpreterError};
+    use std::ffi::OsString;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    fn create_python(venv: &Path, executable: bool) -> PathBuf {
+        let python = venv_python(venv);
+        fs::create_dir_all(python.parent().unwrap()).unwrap();
+        fs::write(&python, "").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = if executable { 0o755 } else { 0o644 };
+            fs::set_permissions(&python, fs::Permissions::from_mode(mode)).unwrap();
+        }
+        python
+    }
+
+    #[test]
+    fn test_find_python_precedence() {
+        let project = std::env::temp_dir().join("jones_test_find_python_precedence");
+        let python = create_python(&project.join(".venv"), true);
+        fs::create_dir_all(project.join(".git")).unwrap();
+        let package = project.join("package");
+        fs::create_dir_all(&package).unwrap();
+
+        let flag = Some(python.clone().into_os_string());
+        assert_eq!(find_python(flag, None, &package), Ok(python.clone()));
+        let found = find_python(None, None, &package).unwrap();
+        assert_eq!(found, python.canonicalize().unwrap());
+
+        let missing = OsString::from("/no/such/python");
+        assert_eq!(
+            find_python(None, Some(missing.clone()), &package),
+            Err(InterpreterError::NotFound {
+                python: missing,
+                origin: "JONES_PYTHON".to_string()
+            })
+        );
+
+        fs::remove_dir_all(&project).unwrap();
+    }
+
+    #[test]
+    fn test_find_venv_python_bounds() {
+        let parent = std::env::temp_dir().join("jones_test_find_venv_python_bounds");
+        create_python(&parent.join("venv"), true);
+        let project = parent.join("project");
+        fs::create_dir_all(project.join(".hg")).unwrap();
+        let package = project.join("package");
+        fs::create_dir_all(&package).unwrap();
+
+        // the venv above the repository does not belong to the project
+        assert_eq!(find_venv_python(&package), None);
+        // outside of a repository only the project directory is looked at
+        assert_eq!(find_venv_python(&parent.join("venv").join("bin")), None);
+
+        #[cfg(unix)]
+        {
+            // a python which cannot be run is passed over
+            create_python(&project.join(".venv"), false);
+            let python = create_python(&project.join("venv"), true);
+            assert_eq!(
+                find_venv_python(&package),
+                Some(python.canonicalize().unwrap())
+            );
+        }
+
+        fs::remove_dir_all(&parent).unwrap();
+    }
+}
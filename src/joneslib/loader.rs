@@ -7,224 +7,305 @@ License: MIT
 
 Copyright 2021 Vlad Nedelcu
 */
-use std::{path::PathBuf, process::Command};
+use std::env;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use super::frameworks::{self, enums};
+use super::index::ProjectIndex;
+use super::interpreter::{self, InterpreterError};
+use super::objects::{Method, ProjectScan, PythonClass};
+use super::parser::ObjectDocument;
+use super::traversal::Traversal;
+use super::worker::{PythonWorker, Script};
+use super::{native, parser, stubs};
+
+/// The parser picked on the command line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BackendKind {
+    Native,
+    Python,
+}
+impl BackendKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            BackendKind::Native => "native",
+            BackendKind::Python => "python",
+        }
+    }
+}
+impl FromStr for BackendKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "native" => Ok(BackendKind::Native),
+            "python" => Ok(BackendKind::Python),
+            _ => Err(format!("Unknown backend: {}", value)),
+        }
+    }
+}
 
-use regex::{Regex, RegexBuilder};
+/// Where the Python sources get parsed: in process, or by scripts run on a
+/// worker started once with the given interpreter.
+#[derive(Debug)]
+pub enum Backend {
+    Native,
+    Python(PythonWorker),
+}
+impl Backend {
+    /// Sets up the picked backend, finding the interpreter of the Python one
+    /// from the `--python` flag, the environment or the project.
+    pub fn new(
+        kind: BackendKind,
+        python: Option<OsString>,
+        project_path: &Path,
+    ) -> Result<Self, InterpreterError> {
+        match kind {
+            BackendKind::Native => Ok(Backend::Native),
+            BackendKind::Python => {
+                let variable = env::var_os(interpreter::PYTHON_VARIABLE);
+                interpreter::find_python(python, variable, project_path)
+                    .map(|python| Backend::Python(PythonWorker::new(python)))
+            }
+        }
+    }
+}
 
-use super::{objects::PythonClass, parser::parse_class};
+/// Loads all definitions from a Python project, given through the python project
+/// path and the traversal picking its files. Files which cannot be parsed are
+/// skipped and listed in the scan. Only the files the index does not know, or
+/// which changed since, get parsed.
+pub fn load_python_project(
+    backend: &Backend,
+    traversal: &Traversal,
+    index: &ProjectIndex,
+    path: &Path,
+) -> Option<ProjectScan> {
+    let files = traversal.python_files(path);
+    index.scan(&files, |files| match backend {
+        Backend::Native => native::load_python_project(files),
+        Backend::Python(worker) => load_script_project(worker, files),
+    })
+}
 
-static CLASS_NAME_PATTERN: &str = r"<Class> (\w+)";
-static FILE_NAME_PATTERN: &str = r"<File> (.+)";
-static METHODS_PATTERN: &str = r"<Methods> (\w+)";
-static ATTRIBUTES_PATTERN: &str = r"<Args>\s\[(.*?)\]";
-static DOCSTRING_PATTERN: &str = r"<DocString> <#(.*)#>";
-static INHERITANCE_PATTERN: &str = r"<Inherit>\s\[(.*?)\]";
-static OUTPUT_PATTERN: &str = r"<Output> (\w+)";
+/// Loads a class from a Python file, given the file path, its dotted module
+/// path and the (qualified) class name.
+pub fn load_python_object(
+    backend: &Backend,
+    index: &ProjectIndex,
+    file_path: &Path,
+    module_path: &str,
+    class_name: &str,
+) -> Option<PythonClass> {
+    let mut python_class = load_backend_object(backend, index, file_path, module_path, class_name)?;
+    if let Some(stub_path) = stubs::stub_path(file_path) {
+        let stub = load_backend_object(backend, index, &stub_path, module_path, class_name)
+            .filter(|stub| stub.qualified_name == python_class.qualified_name);
+        if let Some(stub) = stub {
+            python_class = stubs::merge_class(python_class, stub, &stub_path);
+        }
+    }
+    Some(frameworks::apply(python_class))
+}
 
-/// Loads all objects from a Python project, given through the python project path.
-pub fn load_python_project(path: &PathBuf) -> Option<Vec<(String, String)>> {
-    let class_name_pattern = Regex::new(CLASS_NAME_PATTERN).unwrap();
-    let file_name_pattern = Regex::new(FILE_NAME_PATTERN).unwrap();
+/// Extracts a class with the backend, unless the index has it.
+fn load_backend_object(
+    backend: &Backend,
+    index: &ProjectIndex,
+    file_path: &Path,
+    module_path: &str,
+    class_name: &str,
+) -> Option<PythonClass> {
+    index.class(file_path, module_path, class_name, || match backend {
+        Backend::Native => native::load_python_object(file_path, module_path, class_name),
+        Backend::Python(worker) => load_script_object(worker, file_path, module_path, class_name),
+    })
+}
 
-    let script_output = if path.is_dir() {
-        match run_python_script(&path) {
-            Some(output) => String::from_utf8(output).unwrap(),
-            None => return None,
-        }
-    } else {
-        match run_python_single_file_script(&path) {
-            Some(output) => String::from_utf8(output).unwrap(),
-            None => return None,
-        }
-    };
-
-    let file_pattern_captures = file_name_pattern.captures_iter(&script_output);
-    let found_classes = class_name_pattern
-        .captures_iter(&script_output)
-        .zip(file_pattern_captures)
-        .map(|(class_name, file_name)| (class_name[1].to_string(), file_name[1].to_string()))
-        .collect::<Vec<(String, String)>>();
-
-    Some(found_classes)
-}
-
-pub fn load_python_object(file_path: &PathBuf, class_name: &String) -> Option<PythonClass> {
-    let script_output = match run_python_class_script(file_path, class_name) {
-        Some(output) => String::from_utf8(output).unwrap(),
-        None => return None,
-    };
-
-    let methods_pattern = Regex::new(METHODS_PATTERN).unwrap();
-    let attributes_pattern = Regex::new(ATTRIBUTES_PATTERN).unwrap();
-    let class_pattern = Regex::new(CLASS_NAME_PATTERN).unwrap();
-    let docstring_pattern = RegexBuilder::new(DOCSTRING_PATTERN)
-        .dot_matches_new_line(true)
-        .build()
-        .unwrap();
-    let inheritance_pattern = Regex::new(INHERITANCE_PATTERN).unwrap();
-    let output_pattern = Regex::new(OUTPUT_PATTERN).unwrap();
-
-    let found_parameters = attributes_pattern.captures_iter(&script_output);
-    let found_outputs = output_pattern.captures_iter(&script_output);
-    let found_methods = methods_pattern
-        .captures_iter(&script_output)
-        .zip(found_parameters)
-        .zip(found_outputs)
-        .map(|((method, params), output)| {
-            (
-                method[1].to_string(),
-                params[1].to_string(),
-                output[1].to_string(),
-            )
-        })
-        .collect::<Vec<(String, String, String)>>();
-    let found_class = class_pattern
-        .captures_iter(&script_output)
-        .map(|class_name| class_name[1].to_string())
-        .collect::<Vec<String>>();
-    let found_docstring = docstring_pattern
-        .captures_iter(&script_output)
-        .map(|docstring| docstring[1].to_string())
-        .collect::<Vec<String>>();
-    let found_inheritance = inheritance_pattern
-        .captures_iter(&script_output)
-        .map(|inheritance| inheritance[1].to_string())
-        .collect::<Vec<String>>();
-
-    let class_name = found_class[0].clone();
-    let docstring = found_docstring[0].clone();
-    let inheritance = found_inheritance[0]
-        .clone()
-        .split(", ")
-        .map(|s| s.to_string())
-        .collect::<Vec<String>>();
-
-    Some(parse_class(
-        class_name,
-        found_methods,
-        docstring,
-        inheritance,
-    ))
-}
-
-#[inline]
-fn run_python_script(project_path: &PathBuf) -> Option<Vec<u8>> {
-    let python_script = format!(
-        r#"import os
-import ast
-
-for root, dirs, files in os.walk({:?}):
-    for name in files:
-        if name.endswith(".py"):
-            with open(os.path.join(root, name), 'r') as file:
-                tree = ast.parse(file.read())
-
-            for node in ast.walk(tree):
-                if isinstance(node, ast.ClassDef):
-                    class_name = node.name
-                    print("<Class> %s, <File> %s" % (class_name, os.path.join(root, name)))
-"#,
-        project_path.as_os_str()
-    );
-
-    let output = Command::new("python").arg("-c").arg(python_script).output();
-
-    if let Ok(output) = output {
-        Some(output.stdout)
-    } else {
-        None
+/// Loads a top-level function from a Python file, given the file path, its
+/// dotted module path and the function name.
+pub fn load_python_function(
+    backend: &Backend,
+    index: &ProjectIndex,
+    file_path: &Path,
+    module_path: &str,
+    name: &str,
+) -> Option<Method> {
+    let mut function = load_backend_function(backend, index, file_path, module_path, name)?;
+    let stub = stubs::stub_path(file_path)
+        .and_then(|stub_path| load_backend_function(backend, index, &stub_path, module_path, name));
+    if let Some(stub) = stub {
+        stubs::merge_method_types(&mut function, stub);
     }
+    Some(function)
 }
 
-#[inline]
-fn run_python_single_file_script(file_path: &PathBuf) -> Option<Vec<u8>> {
-    let python_script = format!(
-        r#"import ast
-import os
+fn load_backend_function(
+    backend: &Backend,
+    index: &ProjectIndex,
+    file_path: &Path,
+    module_path: &str,
+    name: &str,
+) -> Option<Method> {
+    index.function(file_path, module_path, name, || match backend {
+        Backend::Native => native::load_python_function(file_path, module_path, name),
+        Backend::Python(worker) => load_script_function(worker, file_path, module_path, name),
+    })
+}
 
-file_name = {:?}
-with open(file_name, "r") as file:
-    tree = ast.parse(file.read())
+fn load_script_project(worker: &PythonWorker, files: &[PathBuf]) -> Option<ProjectScan> {
+    let script_output = run_project_script(worker, files)?;
 
-for node in ast.walk(tree):
-    if not isinstance(node, ast.ClassDef):
-        continue
+    match parser::parse_project(&script_output) {
+        Ok(scan) => Some(scan),
+        Err(error) => {
+            println!("{}", error);
+            None
+        }
+    }
+}
 
-    class_name = node.name
-    print("<Class> %s, <File> %s" % (class_name, file_name))
-"#,
-        file_path.as_os_str(),
-    );
+fn load_script_object(
+    worker: &PythonWorker,
+    file_path: &Path,
+    module_path: &str,
+    class_name: &str,
+) -> Option<PythonClass> {
+    load_script_document(worker, file_path, module_path, class_name)
+        .and_then(parser::document_class)
+}
 
-    let output = Command::new("python").arg("-c").arg(python_script).output();
+fn load_script_function(
+    worker: &PythonWorker,
+    file_path: &Path,
+    module_path: &str,
+    name: &str,
+) -> Option<Method> {
+    load_script_document(worker, file_path, module_path, name).and_then(parser::document_function)
+}
 
-    if let Ok(output) = output {
-        println!("{}", String::from_utf8(output.stderr).unwrap());
-        Some(output.stdout)
-    } else {
-        None
+/// Runs the object script on a file. A file it could not parse is reported
+/// on stderr, as the native backend does.
+fn load_script_document(
+    worker: &PythonWorker,
+    file_path: &Path,
+    module_path: &str,
+    name: &str,
+) -> Option<ObjectDocument> {
+    let script_output = run_object_script(worker, file_path, module_path, name)?;
+
+    match parser::parse_object_document(&script_output) {
+        Ok(document) => {
+            if let Some(skipped) = &document.skipped {
+                eprintln!("{}: {}", skipped.place(), skipped.error);
+            }
+            Some(document)
+        }
+        Err(error) => {
+            println!("{}", error);
+            None
+        }
     }
 }
 
-#[inline]
-fn run_python_class_script(file_path: &PathBuf, class_name: &String) -> Option<Vec<u8>> {
-    let python_script = format!(
-        r#"import ast
-
-with open({:?}, "r") as file:
-    tree = ast.parse(file.read())
-
-def get_method(node_method):
-    method_name = node_method.name
-    method_args = []
-    for arg in node_method.args.args:
-        arg_name = arg.arg
-        if isinstance(arg.annotation, ast.Name):
-            arg_type = arg.annotation.id
-        elif isinstance(arg.annotation, ast.Subscript):
-            arg_type = arg.annotation.value.id
-        elif isinstance(arg.annotation, ast.Attribute):
-            arg_type = arg.annotation.attr
-        else:
-            arg_type = None
-
-        method_value = arg_name + "||" + str(arg_type)
-        method_args.append(method_value)
-
-    return method_name, method_args
-
-def get_output(node_method):
-    if isinstance(node_method.returns, ast.Name):
-        return node_method.returns.id
-    elif isinstance(node_method.returns, ast.Subscript):
-        return node_method.returns.value.id
-    elif isinstance(node_method.returns, ast.Attribute):
-        return node_method.returns.attr
-    else:
-        return None
-
-for node in ast.walk(tree):
-    if isinstance(node, ast.ClassDef) and node.name == "{}":
-        class_name = node.name
-        for m in node.body:
-            if isinstance(m, ast.FunctionDef) or isinstance(m, ast.AsyncFunctionDef):
-                method_name, method_args = get_method(m)
-                print("<Methods> %s, <Args> [%s], <Output> %s" % (method_name, ", ".join(method_args), get_output(m)))
-        print("<Class> %s" % (class_name))
-        print("<DocString> <#%s#>" % (ast.get_docstring(node)))
-        print("<Inherit> [%s]" % (', '.join([b.attr if isinstance(b, ast.Attribute) else b.id for b in node.bases] )))
-        break
-"#,
-        file_path.as_os_str(),
-        class_name
-    );
-
-    let output = Command::new("python").arg("-c").arg(python_script).output();
-
-    if let Ok(output) = output {
-        println!("{}", String::from_utf8(output.stderr.clone()).unwrap());
-        Some(output.stdout)
-    } else {
-        None
+/// Runs a script on the worker and returns the document it made. Failures,
+/// such as a file the script could not parse, are reported on stderr.
+fn run_script(worker: &PythonWorker, script: Script, arguments: Vec<OsString>) -> Option<Vec<u8>> {
+    match worker.run(script, &arguments) {
+        Ok(document) => Some(document),
+        Err(error) => {
+            eprintln!("{}", error);
+            None
+        }
+    }
+}
+
+/// Runs the project script on the files of a project.
+fn run_project_script(worker: &PythonWorker, files: &[PathBuf]) -> Option<Vec<u8>> {
+    let mut arguments = vec![OsString::from(enums::ENUM_BASES.join(","))];
+    arguments.extend(files.iter().map(|file| file.as_os_str().to_os_string()));
+    run_script(worker, Script::Project, arguments)
+}
+
+fn run_object_script(
+    worker: &PythonWorker,
+    file_path: &Path,
+    module_path: &str,
+    name: &str,
+) -> Option<Vec<u8>> {
+    let arguments = vec![
+        file_path.as_os_str().to_os_string(),
+        OsString::from(module_path),
+        OsString::from(name),
+    ];
+    run_script(worker, Script::Object, arguments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load_python_object, load_script_document, Backend};
+    use crate::joneslib::index::ProjectIndex;
+    use crate::joneslib::interpreter;
+    use crate::joneslib::worker::PythonWorker;
+    use std::fs;
+
+    #[test]
+    fn test_backends_render_multiline_annotations() {
+        let python = match interpreter::find_python(None, None, &std::env::temp_dir()) {
+            Ok(python) => python,
+            // nothing to run the Python backend with
+            Err(_) => return,
+        };
+        let test_file = std::env::temp_dir().join("jones_test_backends_render_multiline.py");
+        fs::write(
+            &test_file,
+            "class Theme:\n    def paint(\n        self,\n        colour: Literal[\n            \"#fff\",  # white\n            \"#000\",\n        ],\n        css: str = \"\"\"a {\n  color: red;\n}\"\"\",\n    ): pass\n",
+        )
+        .unwrap();
+
+        let parameters = |backend: &Backend| {
+            let index = ProjectIndex::disabled();
+            load_python_object(backend, &index, &test_file, "theme", "Theme")
+                .unwrap()
+                .methods[0]
+                .parameters
+                .iter()
+                .map(|parameter| (parameter.static_type.clone(), parameter.default.clone()))
+                .collect::<Vec<(String, Option<String>)>>()
+        };
+        let native = parameters(&Backend::Native);
+        assert_eq!(native[1].0, "Literal[\"#fff\", \"#000\",]");
+        assert_eq!(
+            native[2].1.as_deref(),
+            Some("\"\"\"a {\n  color: red;\n}\"\"\"")
+        );
+        assert_eq!(
+            parameters(&Backend::Python(PythonWorker::new(python))),
+            native
+        );
+
+        fs::remove_file(&test_file).unwrap();
+    }
+
+    #[test]
+    fn test_python_backend_skips_unparsable_objects() {
+        let python = match interpreter::find_python(None, None, &std::env::temp_dir()) {
+            Ok(python) => python,
+            // nothing to run the Python backend with
+            Err(_) => return,
+        };
+        let test_file = std::env::temp_dir().join("jones_test_python_backend_skips.py");
+        fs::write(&test_file, "class Broken:\n    x = (1,\n").unwrap();
+
+        let worker = PythonWorker::new(python);
+        let document = load_script_document(&worker, &test_file, "broken", "Broken").unwrap();
+        let skipped = document.skipped.unwrap();
+        assert_eq!(skipped.line, Some(2));
+        assert_eq!(skipped.error, "'(' was never closed");
+        assert!(document.class.is_none());
+
+        fs::remove_file(&test_file).unwrap();
     }
 }
@@ -9,19 +9,36 @@ Copyright 2021 Vlad Nedelcu
 */
 
 pub mod display;
+pub mod encoding;
+pub mod frameworks;
+pub mod index;
+pub mod interpreter;
 pub mod loader;
+pub mod native;
+pub mod notebooks;
 pub mod objects;
 pub mod parser;
+pub mod stubs;
+pub mod traversal;
+pub mod worker;
 
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Component, Path, PathBuf};
+
+use rayon::prelude::*;
+
+use index::ProjectIndex;
+use loader::Backend;
+use objects::{Definition, DefinitionKind, ProjectScan};
+use traversal::Traversal;
 
 const CLASS_TEMPLATE_INHERITANCE: &str = "class {template}(";
 const CLASS_TEMPLATE: &str = "class {template}:";
 const TEMPLATE_KEYWORD: &str = "{template}";
 const PYTHON_EXTENSION: &str = "py";
-
-type ClassMatch = (String, String);
+const STUB_EXTENSION: &str = "pyi";
+const NOTEBOOK_EXTENSION: &str = "ipynb";
+const INIT_MODULE: &str = "__init__";
 
 /// Check if a file contains the searched class by reading the file.
 ///
@@ -31,88 +48,341 @@ type ClassMatch = (String, String);
 ///
 /// # Errors
 /// It panics if the file is cannot be read properly
-fn check_file_contains_class(class_name: &str, file_path: &PathBuf) -> bool {
-    let class_name_inheritance = CLASS_TEMPLATE_INHERITANCE
-        .clone()
-        .replace(TEMPLATE_KEYWORD, class_name);
-    let class_name = CLASS_TEMPLATE.clone().replace(TEMPLATE_KEYWORD, class_name);
-
-    match fs::read_to_string(file_path) {
-        Ok(file_content) => {
+fn check_file_contains_class(class_name: &str, file_path: &Path) -> bool {
+    let class_name_inheritance = CLASS_TEMPLATE_INHERITANCE.replace(TEMPLATE_KEYWORD, class_name);
+    let class_name_colon = CLASS_TEMPLATE.replace(TEMPLATE_KEYWORD, class_name);
+
+    match fs::read(file_path) {
+        Ok(bytes) => {
+            // decoding problems get reported once the file is parsed
+            let file_content = encoding::decode_source(&bytes).text;
             let first_check = file_content.contains(&class_name_inheritance);
-            let second_check = file_content.contains(&class_name);
-            return first_check || second_check;
+            let second_check = file_content.contains(&class_name_colon);
+            first_check || second_check || assigns_name(&file_content, class_name)
         }
-        Err(_) => return false,
-    };
+        Err(_) => false,
+    }
 }
 
-/// Searches recursively through a project for a Python class and extracts that
-/// class into an PythonClass struct.
-pub fn fetch_object_details(path: &PathBuf, class_name: &String) -> Option<objects::PythonClass> {
-    if path.is_file() && check_file_contains_class(class_name, path) {
-        return loader::load_python_object(path, class_name);
-    }
+/// Whether a line assigns the name at the top level, as done for the
+/// functional enums. Notebook lines are quoted in the JSON.
+fn assigns_name(file_content: &str, name: &str) -> bool {
+    file_content.lines().any(|line| {
+        line.trim_start_matches([' ', '"'])
+            .strip_prefix(name)
+            .map(str::trim_start)
+            .is_some_and(|rest| rest.starts_with('=') && !rest.starts_with("=="))
+    })
+}
 
-    recursive_fetch_object(path, class_name)
+/// Whether a file holds Python code: a module, a `.pyi` stub or a notebook.
+pub fn is_python_file(file_path: &Path) -> bool {
+    file_path.extension().is_some_and(|extension| {
+        [PYTHON_EXTENSION, STUB_EXTENSION, NOTEBOOK_EXTENSION]
+            .contains(&extension.to_str().unwrap_or_default())
+    })
 }
 
-fn recursive_fetch_object(dir_path: &PathBuf, class_name: &String) -> Option<objects::PythonClass> {
-    let current_dir = match fs::read_dir(dir_path) {
-        Ok(dir) => dir,
-        Err(err) => {
-            println!("Error occurred while reading dir: {}", err);
-            return None;
+/// How closely a class matches the name requested by the user.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum MatchRank {
+    /// The requested name is the qualified name of the class, optionally
+    /// prefixed by (the end of) its module path: `Outer.Inner`,
+    /// `module.Outer.Inner` or `package.module.Outer.Inner`.
+    Exact,
+    /// The requested name only matches the end of the qualified name, like
+    /// `Meta` for `Order.Meta`.
+    Nested,
+}
+
+/// Ranks the class `qualified_name`, defined in the module `module_path`,
+/// against the requested name. Returns `None` when it does not match at all.
+pub fn match_rank(requested: &str, module_path: &str, qualified_name: &str) -> Option<MatchRank> {
+    if requested == qualified_name {
+        return Some(MatchRank::Exact);
+    }
+    let module_prefix = requested
+        .strip_suffix(qualified_name)
+        .and_then(|prefix| prefix.strip_suffix('.'));
+    if let Some(prefix) = module_prefix {
+        if module_path == prefix || module_path.ends_with(&format!(".{}", prefix)) {
+            return Some(MatchRank::Exact);
         }
+    }
+    if qualified_name.ends_with(&format!(".{}", requested)) {
+        return Some(MatchRank::Nested);
+    }
+    None
+}
+
+/// Dotted module path of a file relative to the search root, the way it
+/// would be imported: `package/module.py` becomes `package.module`.
+pub fn module_path(root: &Path, file_path: &Path) -> String {
+    let relative = match file_path.strip_prefix(root) {
+        Ok(relative) if !relative.as_os_str().is_empty() => relative,
+        _ => Path::new(file_path.file_name().unwrap_or_default()),
     };
+    let mut parts = relative
+        .with_extension("")
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy().to_string()),
+            _ => None,
+        })
+        .collect::<Vec<String>>();
+    if parts.last().is_some_and(|part| part == INIT_MODULE) {
+        parts.pop();
+    }
+    parts.join(".")
+}
 
-    for file in current_dir {
-        let file_path = file.unwrap().path();
-        if file_path.is_dir() {
-            match recursive_fetch_object(&file_path, class_name) {
-                Some(value) => return Some(value),
-                None => continue,
-            };
-        }
+/// Searches through a project for a Python class and extracts that class into
+/// an PythonClass struct. The class name can be qualified; a class matching it
+/// exactly wins over nested classes only ending with that name. The files get
+/// checked for the class in parallel.
+pub fn fetch_object_details(
+    backend: &Backend,
+    traversal: &Traversal,
+    index: &ProjectIndex,
+    path: &Path,
+    class_name: &str,
+) -> Option<objects::PythonClass> {
+    let simple_name = class_name.rsplit('.').next().unwrap_or(class_name);
+    let candidates = traversal
+        .python_files(path)
+        .into_par_iter()
+        .filter(|file_path| is_candidate_file(index, simple_name, file_path))
+        .collect::<Vec<PathBuf>>();
 
-        match file_path.extension() {
-            Some(extension) => {
-                if extension != PYTHON_EXTENSION {
-                    continue;
+    let mut nested_match = None;
+    let mut exact_match = None;
+    for file_path in candidates {
+        let module_path = module_path(path, &file_path);
+        let python_class = match loader::load_python_object(
+            backend,
+            index,
+            &file_path,
+            &module_path,
+            class_name,
+        ) {
+            Some(python_class) => python_class,
+            None => continue,
+        };
+        match match_rank(class_name, &module_path, &python_class.qualified_name) {
+            Some(MatchRank::Exact) => {
+                exact_match = Some(python_class);
+                break;
+            }
+            _ => {
+                if nested_match.is_none() {
+                    nested_match = Some(python_class);
                 }
             }
-            None => continue,
-        }
-        if check_file_contains_class(class_name, &file_path) {
-            return loader::load_python_object(&file_path, &class_name);
         }
     }
-    return None;
+
+    let mut python_class = exact_match.or(nested_match)?;
+    resolve_relations(backend, traversal, index, path, &mut python_class);
+    Some(python_class)
 }
 
-/// Loads the project classes and filters them by the class name. Returns a vector
-/// of tuples containing the class name and the file path.
-pub fn search(path: &PathBuf, class_name: &String) -> Option<Vec<ClassMatch>> {
-    let project_classes = match loader::load_python_project(path) {
-        Some(classes) => classes,
+/// Finds the files defining the classes the relation fields point to, looking
+/// them up among the project classes. A target qualified like `app.Name` is
+/// looked for in the modules with that qualifier in their path, a bare one
+/// first in the package of the class.
+fn resolve_relations(
+    backend: &Backend,
+    traversal: &Traversal,
+    index: &ProjectIndex,
+    path: &Path,
+    python_class: &mut objects::PythonClass,
+) {
+    if python_class
+        .fields
+        .iter()
+        .all(|field| field.relation.is_none())
+    {
+        return;
+    }
+    let project_classes = loader::load_python_project(backend, traversal, index, path)
+        .map(|scan| scan.definitions)
+        .unwrap_or_default();
+    let package = python_class
+        .location
+        .as_ref()
+        .map(|location| parent_module(&module_path(path, Path::new(&location.file))))
+        .unwrap_or_default();
+    for relation in python_class
+        .fields
+        .iter_mut()
+        .filter_map(|field| field.relation.as_mut())
+    {
+        let (qualifier, name) = match relation.target.rsplit_once('.') {
+            Some((qualifier, name)) => (Some(qualifier), name),
+            None => (None, relation.target.as_str()),
+        };
+        let candidates = project_classes
+            .iter()
+            .filter(|definition| {
+                definition.kind == DefinitionKind::Class && definition.name == name
+            })
+            .map(|definition| (module_path(path, Path::new(&definition.file)), definition))
+            .collect::<Vec<(String, &Definition)>>();
+        let found = match qualifier {
+            Some(qualifier) => candidates
+                .iter()
+                .find(|(module, _)| contains_module_parts(module, qualifier)),
+            None => candidates
+                .iter()
+                .find(|(module, _)| parent_module(module) == package)
+                .or_else(|| candidates.first()),
+        };
+        relation.file = found.map(|(_, definition)| definition.file.clone());
+    }
+}
+
+/// The package of a module, empty for a top-level one.
+fn parent_module(module_path: &str) -> String {
+    module_path
+        .rsplit_once('.')
+        .map(|(package, _)| package.to_string())
+        .unwrap_or_default()
+}
+
+/// Whether the dotted parts of a qualifier follow each other in a module path,
+/// like the app label `accounts` in `shop.accounts.models`.
+fn contains_module_parts(module_path: &str, qualifier: &str) -> bool {
+    let module_parts = module_path.split('.').collect::<Vec<&str>>();
+    let qualifier_parts = qualifier.split('.').collect::<Vec<&str>>();
+    module_parts
+        .windows(qualifier_parts.len())
+        .any(|window| window == qualifier_parts.as_slice())
+}
+
+/// Whether a file may define the class, and has to be loaded to find out.
+/// Indexed files are known to, others get read.
+fn is_candidate_file(index: &ProjectIndex, simple_name: &str, file_path: &Path) -> bool {
+    let contains_class = |file_path: &Path| {
+        index
+            .defines_class(file_path, simple_name)
+            .unwrap_or_else(|| check_file_contains_class(simple_name, file_path))
+    };
+    if !contains_class(file_path) {
+        return false;
+    }
+    // the module gets looked up instead, with its stub merged in
+    !stubs::implementation_path(file_path)
+        .is_some_and(|implementation| contains_class(&implementation))
+}
+
+/// Looks up a top-level function through the project definitions, the name
+/// optionally prefixed by (the end of) its module path.
+pub fn fetch_function_details(
+    backend: &Backend,
+    traversal: &Traversal,
+    index: &ProjectIndex,
+    path: &Path,
+    function_name: &str,
+) -> Option<objects::Method> {
+    let mut definitions = loader::load_python_project(backend, traversal, index, path)?.definitions;
+    // modules first, their stubs get merged into them
+    definitions.sort_by_key(|definition| stubs::is_stub(Path::new(&definition.file)));
+    definitions
+        .iter()
+        .filter(|definition| definition.kind == DefinitionKind::Function)
+        .find_map(|definition| {
+            let file_path = Path::new(&definition.file);
+            let module_path = module_path(path, file_path);
+            match match_rank(function_name, &module_path, &definition.name) {
+                Some(MatchRank::Exact) => loader::load_python_function(
+                    backend,
+                    index,
+                    file_path,
+                    &module_path,
+                    function_name,
+                ),
+                _ => None,
+            }
+        })
+}
+
+/// Loads the project definitions (classes, functions and constants) and
+/// filters them by name. The files skipped by the scan are kept.
+pub fn search(
+    backend: &Backend,
+    traversal: &Traversal,
+    index: &ProjectIndex,
+    path: &Path,
+    name: &str,
+) -> Option<ProjectScan> {
+    let scan = match loader::load_python_project(backend, traversal, index, path) {
+        Some(scan) => scan,
         None => {
             println!("Error occurred while loading project classes");
             return None;
         }
     };
 
-    let filtered_classes = project_classes
+    let filtered_definitions = scan
+        .definitions
         .into_iter()
-        .filter(|class| class.0.contains(class_name))
-        .collect::<Vec<ClassMatch>>();
+        .filter(|definition| definition.name.contains(name))
+        .collect::<Vec<Definition>>();
 
-    Some(filtered_classes)
+    Some(ProjectScan {
+        definitions: filtered_definitions,
+        skipped: scan.skipped,
+    })
+}
+
+/// Indexes a whole project again: its definitions, then each of its classes
+/// and functions, extracted as they get looked up by their qualified name.
+pub fn rebuild_index(
+    backend: &Backend,
+    traversal: &Traversal,
+    index: &ProjectIndex,
+    path: &Path,
+) -> Option<ProjectScan> {
+    index.clear();
+    let scan = loader::load_python_project(backend, traversal, index, path)?;
+    scan.definitions.par_iter().for_each(|definition| {
+        let file_path = Path::new(&definition.file);
+        let module_path = module_path(path, file_path);
+        match definition.kind {
+            DefinitionKind::Class => {
+                loader::load_python_object(
+                    backend,
+                    index,
+                    file_path,
+                    &module_path,
+                    &definition.name,
+                );
+            }
+            DefinitionKind::Function => {
+                loader::load_python_function(
+                    backend,
+                    index,
+                    file_path,
+                    &module_path,
+                    &definition.name,
+                );
+            }
+            DefinitionKind::Constant => {}
+        }
+    });
+    Some(scan)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::fetch_object_details;
+    use super::{
+        fetch_function_details, fetch_object_details, match_rank, module_path, search, Backend,
+        Definition, DefinitionKind, MatchRank, ProjectIndex, Traversal,
+    };
+    use crate::joneslib::interpreter;
+    use crate::joneslib::worker::PythonWorker;
     use std::fs;
+    use std::path::Path;
     use std::path::PathBuf;
 
     static PYTHON_CODE: &str = "
@@ -151,7 +421,13 @@ mod tests {
 
         // Assert
         assert_eq!(
-            fetch_object_details(&pathbuf, &"TestCode".to_string()),
+            fetch_object_details(
+                &Backend::Native,
+                &Traversal::default(),
+                &ProjectIndex::disabled(),
+                &pathbuf,
+                "TestCode"
+            ),
             None
         );
 
@@ -173,11 +449,183 @@ mod tests {
 
         // Assert
         assert_eq!(
-            fetch_object_details(&pathbuf, &"TestCode".to_string()),
+            fetch_object_details(
+                &Backend::Native,
+                &Traversal::default(),
+                &ProjectIndex::disabled(),
+                &pathbuf,
+                "TestCode"
+            ),
             None
         );
 
         // Destroy the test dir
         fs::remove_dir_all("./test_fetch_objects").expect("Could not delete dir");
     }
+
+    #[test]
+    fn test_search_and_fetch_native_backend() {
+        // Paths
+        let test_dir = String::from("./test_native_backend");
+        let python_file = String::from("./test_native_backend/test.py");
+        let mut pathbuf = PathBuf::new();
+
+        // Create dir and files
+        fs::create_dir(test_dir).expect("Could not write dir");
+        let source = PYTHON_CODE.replace("\n    ", "\n") + "\ndef pray(god: God):\n    pass\n";
+        fs::write(python_file, source).unwrap();
+        pathbuf.push("./test_native_backend");
+
+        // Assert
+        let (traversal, index) = (Traversal::default(), ProjectIndex::disabled());
+        let matches = search(&Backend::Native, &traversal, &index, &pathbuf, "Go").unwrap();
+        assert_eq!(
+            matches.definitions,
+            vec![Definition::new(
+                "God".to_string(),
+                "./test_native_backend/test.py".to_string(),
+                DefinitionKind::Class
+            )]
+        );
+        let class =
+            fetch_object_details(&Backend::Native, &traversal, &index, &pathbuf, "God").unwrap();
+        assert_eq!(class.docstring, "DocString");
+        assert_eq!(class.methods.len(), 2);
+        assert_eq!(class.methods[0].parameters[0].static_type, "Self");
+        assert_eq!(class.methods[0].parameters[1].static_type, "int");
+        assert_eq!(class.methods[1].output, "None");
+        let function =
+            fetch_function_details(&Backend::Native, &traversal, &index, &pathbuf, "test.pray")
+                .unwrap();
+        assert_eq!(function.parameters[0].name, "god");
+        assert!(fetch_function_details(
+            &Backend::Native,
+            &traversal,
+            &index,
+            &pathbuf,
+            "other.pray"
+        )
+        .is_none());
+
+        // Destroy the test dir
+        fs::remove_dir_all("./test_native_backend").expect("Could not delete dir");
+    }
+
+    #[test]
+    fn test_functional_enum_round_trip() {
+        let project = std::env::temp_dir().join("jones_test_functional_enum_round_trip");
+        fs::create_dir_all(&project).unwrap();
+        fs::write(
+            project.join("colors.py"),
+            "from enum import Enum\n\nShade = Enum('Shade', 'DARK LIGHT')\nShadow = 1\n",
+        )
+        .unwrap();
+
+        let mut backends = vec![Backend::Native];
+        if let Ok(python) = interpreter::find_python(None, None, &project) {
+            backends.push(Backend::Python(PythonWorker::new(python)));
+        }
+        let (traversal, index) = (Traversal::default(), ProjectIndex::disabled());
+        for backend in backends.iter() {
+            let scan = search(backend, &traversal, &index, &project, "Shade").unwrap();
+            assert_eq!(scan.definitions[0].kind, DefinitionKind::Class);
+            // what the search lists as a class can be looked up
+            let class = fetch_object_details(backend, &traversal, &index, &project, "Shade")
+                .expect("the functional enum is found");
+            let members = class
+                .members
+                .iter()
+                .map(|member| (member.name.as_str(), member.value.as_str()))
+                .collect::<Vec<(&str, &str)>>();
+            assert_eq!(members, vec![("DARK", "1"), ("LIGHT", "2")]);
+            assert!(
+                fetch_object_details(backend, &traversal, &index, &project, "Shadow").is_none()
+            );
+        }
+
+        fs::remove_dir_all(&project).unwrap();
+    }
+
+    #[test]
+    fn test_resolve_relations_by_app() {
+        let project = std::env::temp_dir().join("jones_test_resolve_relations_by_app");
+        for app in ["accounts", "billing", "shop"].iter() {
+            fs::create_dir_all(project.join(app)).unwrap();
+        }
+        let customer = "class Customer(models.Model):\n    pass\n";
+        fs::write(project.join("accounts/models.py"), customer).unwrap();
+        fs::write(project.join("billing/models.py"), customer).unwrap();
+        let order = "
+class Order(models.Model):
+    payer = models.ForeignKey('billing.Customer', on_delete=models.CASCADE)
+    buyer = models.ForeignKey('Customer', on_delete=models.CASCADE)
+";
+        fs::write(
+            project.join("shop/models.py"),
+            format!("{}{}", customer, order),
+        )
+        .unwrap();
+
+        let (traversal, index) = (Traversal::default(), ProjectIndex::disabled());
+        let class =
+            fetch_object_details(&Backend::Native, &traversal, &index, &project, "Order").unwrap();
+        let files = class
+            .fields
+            .iter()
+            .map(|field| {
+                field
+                    .relation
+                    .as_ref()
+                    .and_then(|relation| relation.file.clone())
+            })
+            .collect::<Vec<Option<String>>>();
+        let file = |app: &str| Some(project.join(app).join("models.py").display().to_string());
+        // the app label picks the model, a bare name is looked for in the same app
+        assert_eq!(files, vec![file("billing"), file("shop")]);
+
+        fs::remove_dir_all(&project).unwrap();
+    }
+
+    #[test]
+    fn test_match_rank() {
+        assert_eq!(
+            match_rank("Inner", "pkg.mod", "Inner"),
+            Some(MatchRank::Exact)
+        );
+        assert_eq!(
+            match_rank("Outer.Inner", "pkg.mod", "Outer.Inner"),
+            Some(MatchRank::Exact)
+        );
+        assert_eq!(
+            match_rank("pkg.mod.Outer.Inner", "pkg.mod", "Outer.Inner"),
+            Some(MatchRank::Exact)
+        );
+        assert_eq!(
+            match_rank("mod.Outer.Inner", "pkg.mod", "Outer.Inner"),
+            Some(MatchRank::Exact)
+        );
+        assert_eq!(
+            match_rank("Inner", "pkg.mod", "Outer.Inner"),
+            Some(MatchRank::Nested)
+        );
+        assert_eq!(match_rank("od.Outer.Inner", "pkg.mod", "Outer.Inner"), None);
+        assert_eq!(match_rank("Outer", "pkg.mod", "Outer.Inner"), None);
+    }
+
+    #[test]
+    fn test_module_path() {
+        let root = Path::new("./project");
+        assert_eq!(
+            module_path(root, Path::new("./project/shop/models.py")),
+            "shop.models"
+        );
+        assert_eq!(
+            module_path(root, Path::new("./project/shop/__init__.py")),
+            "shop"
+        );
+        assert_eq!(
+            module_path(Path::new("./models.py"), Path::new("./models.py")),
+            "models"
+        );
+    }
 }
@@ -0,0 +1,726 @@
+/*
+JonesCLI
+
+Author: Vlad Nedelcu
+Date: Jul 2021
+License: MIT
+
+Copyright 2021 Vlad Nedelcu
+*/
+use std::fmt;
+
+const TAB_SIZE: usize = 8;
+const THREE_CHAR_OPERATORS: [&str; 5] = ["**=", "//=", ">>=", "<<=", "..."];
+const TWO_CHAR_OPERATORS: [&str; 20] = [
+    "->", "**", "//", "==", "!=", "<=", ">=", "<<", ">>", "+=", "-=", "*=", "/=", "%=", "&=", "|=",
+    "^=", "@=", ":=", "<>",
+];
+const OPENING_BRACKETS: [&str; 3] = ["(", "[", "{"];
+const CLOSING_BRACKETS: [&str; 3] = [")", "]", "}"];
+const OPERATOR_CHARACTERS: &str = "()[]{}:;,.+-*/%&|^~<>=!@";
+const STRING_PREFIXES: [&str; 12] = [
+    "", "r", "u", "b", "f", "t", "br", "rb", "fr", "rf", "tr", "rt",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenKind {
+    Name,
+    Number,
+    String,
+    Operator,
+    Newline,
+    Indent,
+    Dedent,
+    EndOfFile,
+}
+
+/// A line (1-based) and column (0-based, in bytes) inside a source file, the
+/// same convention the Python `ast` module uses.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub text: String,
+    pub start: Position,
+    pub end: Position,
+    pub offset: usize,
+    pub end_offset: usize,
+}
+impl Token {
+    pub fn is_operator(&self, value: &str) -> bool {
+        self.kind == TokenKind::Operator && self.text == value
+    }
+
+    pub fn is_name(&self, value: &str) -> bool {
+        self.kind == TokenKind::Name && self.text == value
+    }
+
+    pub fn is_opening_bracket(&self) -> bool {
+        self.kind == TokenKind::Operator && OPENING_BRACKETS.contains(&self.text.as_str())
+    }
+
+    pub fn is_closing_bracket(&self) -> bool {
+        self.kind == TokenKind::Operator && CLOSING_BRACKETS.contains(&self.text.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxError {
+    pub message: String,
+    pub position: Position,
+}
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} (line {}, column {})",
+            self.message, self.position.line, self.position.column
+        )
+    }
+}
+
+/// Splits Python source into tokens, following the rules of the CPython
+/// tokenizer closely enough to recover the structure of a module: implicit
+/// line joining inside brackets, backslash continuations and INDENT/DEDENT
+/// tokens for blocks. Comments and blank lines are dropped.
+pub fn tokenize(source: &str) -> Result<Vec<Token>, SyntaxError> {
+    match tokenize_partial(source) {
+        (_, Some(error)) => Err(error),
+        (tokens, None) => Ok(tokens),
+    }
+}
+
+/// Like `tokenize`, but on an error also returns the complete logical lines
+/// read before it, so the parser can report an earlier error first.
+pub fn tokenize_partial(source: &str) -> (Vec<Token>, Option<SyntaxError>) {
+    let mut lexer = Lexer::new(source);
+    let error = lexer.run().err();
+    if error.is_some() {
+        let complete = lexer
+            .tokens
+            .iter()
+            .rposition(|token| token.kind == TokenKind::Newline)
+            .map_or(0, |index| index + 1);
+        lexer.tokens.truncate(complete);
+    }
+    lexer.finish();
+    (lexer.tokens, error)
+}
+
+struct Lexer<'a> {
+    source: &'a str,
+    offset: usize,
+    line: usize,
+    line_start: usize,
+    brackets: Vec<(String, Position)>,
+    indents: Vec<usize>,
+    tokens: Vec<Token>,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(source: &'a str) -> Self {
+        Lexer {
+            source,
+            offset: 0,
+            line: 1,
+            line_start: 0,
+            brackets: Vec::new(),
+            indents: vec![0],
+            tokens: Vec::new(),
+        }
+    }
+
+    fn position(&self) -> Position {
+        Position {
+            line: self.line,
+            column: self.offset - self.line_start,
+        }
+    }
+
+    fn error(&self, message: &str) -> SyntaxError {
+        SyntaxError {
+            message: message.to_string(),
+            position: self.position(),
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.source[self.offset..].chars().next()
+    }
+
+    fn peek_at(&self, skip: usize) -> Option<char> {
+        self.source[self.offset..].chars().nth(skip)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let character = self.peek()?;
+        self.offset += character.len_utf8();
+        if character == '\n' {
+            self.line += 1;
+            self.line_start = self.offset;
+        }
+        Some(character)
+    }
+
+    fn push(&mut self, kind: TokenKind, start: Position, offset: usize) {
+        self.tokens.push(Token {
+            kind,
+            text: self.source[offset..self.offset].to_string(),
+            start,
+            end: self.position(),
+            offset,
+            end_offset: self.offset,
+        });
+    }
+
+    fn push_marker(&mut self, kind: TokenKind) {
+        let position = self.position();
+        self.tokens.push(Token {
+            kind,
+            text: String::new(),
+            start: position,
+            end: position,
+            offset: self.offset,
+            end_offset: self.offset,
+        });
+    }
+
+    fn last_is_logical_end(&self) -> bool {
+        match self.tokens.last() {
+            Some(token) => matches!(
+                token.kind,
+                TokenKind::Newline | TokenKind::Indent | TokenKind::Dedent
+            ),
+            None => true,
+        }
+    }
+
+    fn run(&mut self) -> Result<(), SyntaxError> {
+        let mut at_line_start = true;
+        loop {
+            if at_line_start {
+                at_line_start = false;
+                if !self.handle_indentation()? {
+                    break;
+                }
+            }
+
+            let character = match self.peek() {
+                Some(character) => character,
+                None => break,
+            };
+            let start = self.position();
+            let offset = self.offset;
+
+            match character {
+                ' ' | '\t' | '\x0c' => {
+                    self.bump();
+                }
+                '\r' if self.peek_at(1) == Some('\n') => {
+                    self.bump();
+                }
+                '#' => self.skip_comment(),
+                '\\' if matches!(self.peek_at(1), Some('\n') | Some('\r')) => {
+                    self.bump();
+                    if self.peek() == Some('\r') {
+                        self.bump();
+                    }
+                    self.bump();
+                }
+                '\n' | '\r' => {
+                    self.bump();
+                    if self.brackets.is_empty() {
+                        if !self.last_is_logical_end() {
+                            self.tokens.push(Token {
+                                kind: TokenKind::Newline,
+                                text: "\n".to_string(),
+                                start,
+                                end: start,
+                                offset,
+                                end_offset: offset,
+                            });
+                        }
+                        at_line_start = true;
+                    }
+                }
+                '\\' => {
+                    return Err(self.error("unexpected character after line continuation character"))
+                }
+                _ if self.starts_string() => self.read_string(start, offset)?,
+                _ if self.starts_invalid_string() => {
+                    let prefix_length = self.string_prefix().unwrap_or_default().len();
+                    return Err(SyntaxError {
+                        message: "invalid syntax".to_string(),
+                        position: Position {
+                            line: start.line,
+                            column: start.column + prefix_length,
+                        },
+                    });
+                }
+                _ if character.is_ascii_digit()
+                    || (character == '.'
+                        && self.peek_at(1).is_some_and(|c| c.is_ascii_digit())) =>
+                {
+                    self.read_number(start, offset)?
+                }
+                _ if is_identifier_start(character) => {
+                    while self.peek().is_some_and(is_identifier_continue) {
+                        self.bump();
+                    }
+                    self.push(TokenKind::Name, start, offset);
+                }
+                _ if !OPERATOR_CHARACTERS.contains(character) => {
+                    return Err(invalid_character(character, start))
+                }
+                _ => self.read_operator(start, offset)?,
+            }
+        }
+
+        match self.brackets.last() {
+            Some((bracket, position)) => Err(SyntaxError {
+                message: format!("'{}' was never closed", bracket),
+                position: *position,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Closes the last logical line and the open blocks.
+    fn finish(&mut self) {
+        if !self.last_is_logical_end() {
+            self.push_marker(TokenKind::Newline);
+        }
+        while self.indents.len() > 1 {
+            self.indents.pop();
+            self.push_marker(TokenKind::Dedent);
+        }
+        self.push_marker(TokenKind::EndOfFile);
+    }
+
+    /// Measures the indentation of the next non-blank line and emits the
+    /// matching INDENT/DEDENT tokens. Returns false once the input is over.
+    fn handle_indentation(&mut self) -> Result<bool, SyntaxError> {
+        loop {
+            let mut width = 0;
+            while let Some(character) = self.peek() {
+                match character {
+                    ' ' => width += 1,
+                    '\t' => width = (width / TAB_SIZE + 1) * TAB_SIZE,
+                    '\x0c' => width = 0,
+                    _ => break,
+                }
+                self.bump();
+            }
+
+            match self.peek() {
+                None => return Ok(false),
+                Some('#') => {
+                    self.skip_comment();
+                    continue;
+                }
+                Some('\n') | Some('\r') => {
+                    self.bump();
+                    continue;
+                }
+                Some('\\') if matches!(self.peek_at(1), Some('\n') | Some('\r')) => {
+                    return Ok(true);
+                }
+                _ => {}
+            }
+
+            let current = *self.indents.last().unwrap_or(&0);
+            if width > current {
+                self.indents.push(width);
+                self.push_marker(TokenKind::Indent);
+            } else {
+                while width < *self.indents.last().unwrap_or(&0) {
+                    self.indents.pop();
+                    self.push_marker(TokenKind::Dedent);
+                }
+                if width != *self.indents.last().unwrap_or(&0) {
+                    return Err(self.error("unindent does not match any outer indentation level"));
+                }
+            }
+            return Ok(true);
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        while let Some(character) = self.peek() {
+            if character == '\n' || character == '\r' {
+                break;
+            }
+            self.bump();
+        }
+    }
+
+    /// The letters before a quote at the current offset, if a string starts
+    /// here, whether or not they make a valid prefix.
+    fn string_prefix(&self) -> Option<&str> {
+        let rest = &self.source[self.offset..];
+        let prefix_length = rest
+            .chars()
+            .take_while(|c| matches!(c.to_ascii_lowercase(), 'r' | 'b' | 'u' | 'f' | 't'))
+            .count();
+        matches!(rest[prefix_length..].chars().next(), Some('\'') | Some('"'))
+            .then(|| &rest[..prefix_length])
+    }
+
+    fn starts_string(&self) -> bool {
+        self.string_prefix()
+            .is_some_and(|prefix| STRING_PREFIXES.contains(&prefix.to_ascii_lowercase().as_str()))
+    }
+
+    /// A Python 2 prefix such as `ur`, or letters glued to a string.
+    fn starts_invalid_string(&self) -> bool {
+        self.string_prefix().is_some() && !self.starts_string()
+    }
+
+    fn unterminated_string(&self, start: Position, triple: bool) -> SyntaxError {
+        let detected_line = if self.source[..self.offset].ends_with('\n') {
+            self.line - 1
+        } else {
+            self.line
+        };
+        let kind = if triple { "triple-quoted " } else { "" };
+        SyntaxError {
+            message: format!(
+                "unterminated {}string literal (detected at line {})",
+                kind, detected_line
+            ),
+            position: start,
+        }
+    }
+
+    fn read_string(&mut self, start: Position, offset: usize) -> Result<(), SyntaxError> {
+        while let Some(character) = self.peek() {
+            if character == '\'' || character == '"' {
+                break;
+            }
+            self.bump();
+        }
+        let quote = self.bump().unwrap_or('"');
+        let triple = self.peek() == Some(quote) && self.peek_at(1) == Some(quote);
+        if triple {
+            self.bump();
+            self.bump();
+        }
+
+        loop {
+            let character = match self.bump() {
+                Some(character) => character,
+                None => return Err(self.unterminated_string(start, triple)),
+            };
+            match character {
+                '\\' => {
+                    self.bump();
+                }
+                '\n' if !triple => return Err(self.unterminated_string(start, triple)),
+                _ if character == quote => {
+                    if !triple {
+                        break;
+                    }
+                    if self.peek() == Some(quote) && self.peek_at(1) == Some(quote) {
+                        self.bump();
+                        self.bump();
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        self.push(TokenKind::String, start, offset);
+        Ok(())
+    }
+
+    fn read_number(&mut self, start: Position, offset: usize) -> Result<(), SyntaxError> {
+        let mut previous = ' ';
+        while let Some(character) = self.peek() {
+            let exponent_sign = (character == '+' || character == '-')
+                && (previous == 'e' || previous == 'E')
+                && !self.source[offset..self.offset].starts_with("0x")
+                && !self.source[offset..self.offset].starts_with("0X");
+            if !(character.is_ascii_alphanumeric()
+                || character == '_'
+                || character == '.'
+                || exponent_sign)
+            {
+                break;
+            }
+            previous = character;
+            self.bump();
+        }
+        if let Some(message) = check_number(&self.source[offset..self.offset]) {
+            return Err(SyntaxError {
+                message: message.to_string(),
+                position: start,
+            });
+        }
+        self.push(TokenKind::Number, start, offset);
+        Ok(())
+    }
+
+    fn read_operator(&mut self, start: Position, offset: usize) -> Result<(), SyntaxError> {
+        let rest = &self.source[self.offset..];
+        let length = if THREE_CHAR_OPERATORS.iter().any(|op| rest.starts_with(op)) {
+            3
+        } else if TWO_CHAR_OPERATORS.iter().any(|op| rest.starts_with(op)) {
+            2
+        } else {
+            1
+        };
+
+        for _ in 0..length {
+            self.bump();
+        }
+        let text = &self.source[offset..self.offset];
+        if text == "<>" {
+            return Err(SyntaxError {
+                message: "invalid syntax".to_string(),
+                position: start,
+            });
+        }
+        if OPENING_BRACKETS.contains(&text) {
+            self.brackets.push((text.to_string(), start));
+        } else if let Some(closing) = CLOSING_BRACKETS.iter().position(|bracket| *bracket == text) {
+            let (opening, position) = match self.brackets.pop() {
+                Some(bracket) => bracket,
+                None => {
+                    return Err(SyntaxError {
+                        message: format!("unmatched '{}'", text),
+                        position: start,
+                    })
+                }
+            };
+            if opening != OPENING_BRACKETS[closing] {
+                let on_line = if position.line == start.line {
+                    String::new()
+                } else {
+                    format!(" on line {}", position.line)
+                };
+                return Err(SyntaxError {
+                    message: format!(
+                        "closing parenthesis '{}' does not match opening parenthesis '{}'{}",
+                        text, opening, on_line
+                    ),
+                    position: start,
+                });
+            }
+        }
+        self.push(TokenKind::Operator, start, offset);
+        Ok(())
+    }
+}
+
+/// Rejects the Python 2 `L` suffix and octal literals written with a
+/// leading zero, which the number scan above accepts.
+fn check_number(text: &str) -> Option<&'static str> {
+    let lowered = text.to_ascii_lowercase();
+    let (kind, digits) = match lowered.get(..2) {
+        Some("0x") => ("hexadecimal", &lowered[2..]),
+        Some("0o") => ("octal", &lowered[2..]),
+        Some("0b") => ("binary", &lowered[2..]),
+        _ => ("decimal", lowered.as_str()),
+    };
+    if let Some(value) = digits.strip_suffix('l') {
+        if !value.is_empty() && value.chars().all(|c| c.is_ascii_hexdigit() || c == '_') {
+            return Some(match kind {
+                "hexadecimal" => "invalid hexadecimal literal",
+                "octal" => "invalid octal literal",
+                "binary" => "invalid binary literal",
+                _ => "invalid decimal literal",
+            });
+        }
+    }
+    let octal_like = kind == "decimal"
+        && lowered.starts_with('0')
+        && lowered.chars().all(|c| c.is_ascii_digit() || c == '_')
+        && lowered.chars().any(|c| c.is_ascii_digit() && c != '0');
+    if octal_like {
+        return Some(
+            "leading zeros in decimal integer literals are not permitted; \
+             use an 0o prefix for octal integers",
+        );
+    }
+    None
+}
+
+fn invalid_character(character: char, position: Position) -> SyntaxError {
+    let message = if character.is_ascii_graphic() {
+        "invalid syntax".to_string()
+    } else if is_printable(character) {
+        format!(
+            "invalid character '{}' (U+{:04X})",
+            character, character as u32
+        )
+    } else {
+        format!("invalid non-printable character U+{:04X}", character as u32)
+    };
+    SyntaxError { message, position }
+}
+
+fn is_printable(character: char) -> bool {
+    !(character.is_control()
+        || character.is_whitespace()
+        || matches!(
+            character,
+            '\u{ad}' | '\u{200b}'..='\u{200f}' | '\u{2028}'..='\u{202e}' | '\u{2060}'..='\u{2064}'
+                | '\u{feff}'
+        ))
+}
+
+fn is_identifier_start(character: char) -> bool {
+    character == '_' || character.is_alphabetic()
+}
+
+/// Besides letters and digits, identifiers may hold combining marks, as in a
+/// decomposed `café`, variation selectors and the middle dot.
+fn is_identifier_continue(character: char) -> bool {
+    is_identifier_start(character)
+        || character.is_alphanumeric()
+        || matches!(
+            character,
+            '\u{b7}'
+                | '\u{387}'
+                | '\u{300}'..='\u{36f}'
+                | '\u{1ab0}'..='\u{1aff}'
+                | '\u{1dc0}'..='\u{1dff}'
+                | '\u{20d0}'..='\u{20ff}'
+                | '\u{fe00}'..='\u{fe0f}'
+                | '\u{fe20}'..='\u{fe2f}'
+                | '\u{e0100}'..='\u{e01ef}'
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{tokenize, TokenKind};
+
+    fn kinds(source: &str) -> Vec<TokenKind> {
+        tokenize(source)
+            .unwrap()
+            .into_iter()
+            .map(|token| token.kind)
+            .collect()
+    }
+
+    #[test]
+    fn test_tokenize_blocks() {
+        let source = "class A:\n    def f(self):\n        pass\n\nx = 1\n";
+        assert_eq!(
+            kinds(source),
+            vec![
+                TokenKind::Name,
+                TokenKind::Name,
+                TokenKind::Operator,
+                TokenKind::Newline,
+                TokenKind::Indent,
+                TokenKind::Name,
+                TokenKind::Name,
+                TokenKind::Operator,
+                TokenKind::Name,
+                TokenKind::Operator,
+                TokenKind::Operator,
+                TokenKind::Newline,
+                TokenKind::Indent,
+                TokenKind::Name,
+                TokenKind::Newline,
+                TokenKind::Dedent,
+                TokenKind::Dedent,
+                TokenKind::Name,
+                TokenKind::Operator,
+                TokenKind::Number,
+                TokenKind::Newline,
+                TokenKind::EndOfFile,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_implicit_line_joining() {
+        let source = "x = call(\n    1,\n  2)  # comment\n";
+        let tokens = tokenize(source).unwrap();
+        let newlines = tokens
+            .iter()
+            .filter(|token| token.kind == TokenKind::Newline)
+            .count();
+        assert_eq!(newlines, 1);
+        assert!(!tokens.iter().any(|token| token.kind == TokenKind::Indent));
+    }
+
+    #[test]
+    fn test_tokenize_strings() {
+        let source = "a = '''multi\n'line'\n''' + rb\"x\\\"y\" + f'{a}'\n";
+        let strings = tokenize(source)
+            .unwrap()
+            .into_iter()
+            .filter(|token| token.kind == TokenKind::String)
+            .map(|token| token.text)
+            .collect::<Vec<String>>();
+        assert_eq!(
+            strings,
+            vec!["'''multi\n'line'\n'''", "rb\"x\\\"y\"", "f'{a}'"]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_bad_dedent() {
+        let source = "if x:\n        y\n    z\n";
+        assert!(tokenize(source).is_err());
+    }
+
+    fn error(source: &str) -> (String, usize) {
+        let error = tokenize(source).unwrap_err();
+        (error.message, error.position.line)
+    }
+
+    #[test]
+    fn test_tokenize_brackets() {
+        assert_eq!(
+            error("x = call(\n    [1,\n    2]\n"),
+            ("'(' was never closed".to_string(), 1)
+        );
+        assert_eq!(
+            error("x = (1,\n     2]\n").0,
+            "closing parenthesis ']' does not match opening parenthesis '(' on line 1"
+        );
+        assert_eq!(error("x = 1)\n").0, "unmatched ')'");
+    }
+
+    #[test]
+    fn test_tokenize_invalid_literals() {
+        assert!(error("mode = 0777\n").0.starts_with("leading zeros"));
+        assert_eq!(error("x = 10L\n").0, "invalid decimal literal");
+        assert_eq!(error("x = 0xFFL\n").0, "invalid hexadecimal literal");
+        assert_eq!(error("x = ur'a'\n").0, "invalid syntax");
+        assert_eq!(
+            error("x = \"\"\"a\n\nb\n"),
+            (
+                "unterminated triple-quoted string literal (detected at line 3)".to_string(),
+                1
+            )
+        );
+        assert!(tokenize("x = 00 + 0_0 + 09.5 + 0e1 + 01j + 0o777\n").is_ok());
+    }
+
+    #[test]
+    fn test_tokenize_characters() {
+        assert_eq!(error("x = `y`\n").0, "invalid syntax");
+        assert_eq!(error("x = 1 <> 2\n").0, "invalid syntax");
+        assert_eq!(
+            error("price = 3\u{20ac}\n").0,
+            "invalid character '\u{20ac}' (U+20AC)"
+        );
+        assert_eq!(
+            error("x = 1\u{a0}\n").0,
+            "invalid non-printable character U+00A0"
+        );
+        assert!(tokenize("cafe\u{301} = x\u{b7}y\n").is_ok());
+    }
+}
@@ -0,0 +1,831 @@
+/*
+JonesCLI
+
+Author: Vlad Nedelcu
+Date: Jul 2021
+License: MIT
+
+Copyright 2021 Vlad Nedelcu
+*/
+pub mod lexer;
+pub mod syntax;
+
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+
+use super::encoding;
+use super::frameworks::{enums, simple_name};
+use super::notebooks::{self, NotebookSource};
+use super::objects::{
+    Attribute, AttributeScope, Definition, DefinitionKind, Keyword, Location, Method, MethodKind,
+    Parameter, ProjectScan, PythonClass, SkippedFile, TypeSource,
+};
+use super::{match_rank, MatchRank};
+use lexer::Position;
+use syntax::{Assignment, ClassDef, Expression, FunctionDef, Module, Statement};
+
+const MISSING_VALUE: &str = "None";
+const CONSTRUCTOR: &str = "__init__";
+const FINAL_ANNOTATION: &str = "Final";
+
+/// The file definitions are extracted from, turning parser positions into
+/// the locations reported to the user.
+pub struct SourceFile {
+    pub path: String,
+    /// The cells of a notebook, whose code got joined before parsing.
+    pub cells: Option<NotebookSource>,
+}
+impl SourceFile {
+    pub fn new(path: &Path) -> Self {
+        SourceFile {
+            path: path.display().to_string(),
+            cells: None,
+        }
+    }
+
+    pub fn location(&self, start: Position, end: Position) -> Location {
+        let location = Location {
+            file: self.path.clone(),
+            line: start.line,
+            column: start.column,
+            end_line: end.line,
+            end_column: end.column,
+            cell: None,
+        };
+        let cells = match &self.cells {
+            Some(cells) => cells,
+            None => return location,
+        };
+        match (cells.cell_line(start.line), cells.cell_line(end.line)) {
+            (Some((cell, line)), Some((_, end_line))) => Location {
+                line,
+                end_line,
+                cell: Some(cell),
+                ..location
+            },
+            _ => location,
+        }
+    }
+}
+
+/// Loads all definitions from the files of a Python project, the same way
+/// the Python script backend does, without spawning an interpreter. The files
+/// are parsed in parallel, their definitions kept in the order given.
+pub fn load_python_project(files: &[PathBuf]) -> Option<ProjectScan> {
+    let results = files
+        .par_iter()
+        .map(|file_path| {
+            let (module, _) = parse_source(file_path)?;
+            let mut definitions = Vec::new();
+            push_module_definitions(&module, file_path, &mut definitions);
+            Ok(definitions)
+        })
+        .collect::<Vec<Result<Vec<Definition>, SkippedFile>>>();
+
+    let mut scan = ProjectScan::default();
+    for result in results {
+        match result {
+            Ok(definitions) => scan.definitions.extend(definitions),
+            Err(skipped) => scan.skipped.push(skipped),
+        }
+    }
+    Some(scan)
+}
+
+/// Pushes the classes of a module, functional enums included, then its
+/// top-level functions and constants.
+fn push_module_definitions(module: &Module, path: &Path, definitions: &mut Vec<Definition>) {
+    let file = path.display().to_string();
+    let mut push = |name: String, kind: DefinitionKind| {
+        definitions.push(Definition::new(name, file.clone(), kind));
+    };
+
+    for (qualified_name, _) in module.qualified_classes() {
+        push(qualified_name, DefinitionKind::Class);
+    }
+    let assignments = module
+        .body
+        .iter()
+        .filter_map(Statement::assignment)
+        .filter(|assignment| assignment.value.is_some() && !assignment.is_unpacking())
+        .collect::<Vec<Assignment>>();
+    let is_enum = |assignment: &Assignment| {
+        assignment
+            .value
+            .as_ref()
+            .is_some_and(|value| enums::is_functional_enum(&value.text()))
+    };
+    for assignment in assignments.iter().filter(|assignment| is_enum(assignment)) {
+        if assignment.targets.len() == 1 {
+            assignment
+                .names()
+                .into_iter()
+                .for_each(|name| push(name, DefinitionKind::Class));
+        }
+    }
+    for statement in module.body.iter() {
+        if let Statement::Function(function) = statement {
+            push(function.name.clone(), DefinitionKind::Function);
+        }
+    }
+    for assignment in assignments.iter().filter(|assignment| !is_enum(assignment)) {
+        let is_final = assignment
+            .annotation
+            .as_ref()
+            .is_some_and(|annotation| simple_name(&annotation.text()) == FINAL_ANNOTATION);
+        for name in assignment.names() {
+            if is_final || is_constant_name(&name) {
+                push(name, DefinitionKind::Constant);
+            }
+        }
+    }
+}
+
+/// Constants are named in upper case by convention, `MAX_SIZE` or `_DEFAULTS`.
+fn is_constant_name(name: &str) -> bool {
+    let name = name.trim_start_matches('_');
+    name.starts_with(|character: char| character.is_ascii_uppercase())
+        && name.chars().all(|character| {
+            character.is_ascii_uppercase() || character.is_ascii_digit() || character == '_'
+        })
+}
+
+/// Parses a file and extracts the top-level function called by the given,
+/// possibly module qualified, name.
+pub fn load_python_function(file_path: &Path, module_path: &str, name: &str) -> Option<Method> {
+    let (module, source) = parse_file(file_path)?;
+    module.body.iter().find_map(|statement| match statement {
+        Statement::Function(function)
+            if match_rank(name, module_path, &function.name) == Some(MatchRank::Exact) =>
+        {
+            Some(extract_function(&source, function))
+        }
+        _ => None,
+    })
+}
+
+/// Parses a file and extracts the class best matching the given, possibly
+/// qualified, name. A top-level functional enum is built when no class
+/// statement matches.
+pub fn load_python_object(
+    file_path: &Path,
+    module_path: &str,
+    class_name: &str,
+) -> Option<PythonClass> {
+    let (module, source) = parse_file(file_path)?;
+    let mut best_match: Option<(MatchRank, String, &ClassDef)> = None;
+    for (qualified_name, class) in module.qualified_classes() {
+        let rank = match match_rank(class_name, module_path, &qualified_name) {
+            Some(rank) => rank,
+            None => continue,
+        };
+        if best_match
+            .as_ref()
+            .is_none_or(|(best_rank, _, _)| rank < *best_rank)
+        {
+            best_match = Some((rank, qualified_name, class));
+        }
+    }
+
+    match best_match {
+        Some((_, qualified_name, class)) => Some(extract_class(&source, class, &qualified_name)),
+        None => load_functional_enum(&module, &source, module_path, class_name),
+    }
+}
+
+fn load_functional_enum(
+    module: &Module,
+    source: &SourceFile,
+    module_path: &str,
+    class_name: &str,
+) -> Option<PythonClass> {
+    module
+        .body
+        .iter()
+        .filter_map(Statement::assignment)
+        .filter(|assignment| assignment.targets.len() == 1)
+        .find_map(|assignment| {
+            let names = assignment.names();
+            let name = match names.as_slice() {
+                [name] if match_rank(class_name, module_path, name) == Some(MatchRank::Exact) => {
+                    name
+                }
+                _ => return None,
+            };
+            let location = source.location(assignment.start, assignment.end);
+            enums::functional_class(name, &assignment.value.as_ref()?.text(), Some(location))
+        })
+}
+
+/// Parses a module, reporting the files which cannot be parsed on stderr.
+fn parse_file(file_path: &Path) -> Option<(Module, SourceFile)> {
+    match parse_source(file_path) {
+        Ok(parsed) => Some(parsed),
+        Err(skipped) => {
+            eprintln!("{}: {}", skipped.place(), skipped.error);
+            None
+        }
+    }
+}
+
+/// Parses a module, or the code cells of a notebook.
+fn parse_source(file_path: &Path) -> Result<(Module, SourceFile), SkippedFile> {
+    let skipped = |error: String| SkippedFile {
+        file: file_path.display().to_string(),
+        error,
+        line: None,
+        cell: None,
+    };
+    let mut source_file = SourceFile::new(file_path);
+    let source = if notebooks::is_notebook(file_path) {
+        let notebook = notebooks::read_notebook(file_path).map_err(skipped)?;
+        let source = notebook.source.clone();
+        source_file.cells = Some(notebook);
+        source
+    } else {
+        encoding::read_source(file_path).map_err(skipped)?
+    };
+    match syntax::parse_module(&source) {
+        Ok(module) => Ok((module, source_file)),
+        Err(error) => {
+            let location = source_file.location(error.position, error.position);
+            Err(SkippedFile {
+                line: Some(location.line),
+                cell: location.cell,
+                ..skipped(error.message)
+            })
+        }
+    }
+}
+
+pub fn extract_class(source: &SourceFile, class: &ClassDef, qualified_name: &str) -> PythonClass {
+    let methods = class
+        .body
+        .iter()
+        .filter_map(|statement| match statement {
+            Statement::Function(function) => Some(extract_method(source, function)),
+            _ => None,
+        })
+        .collect::<Vec<Method>>();
+    let inheritance = class
+        .bases()
+        .into_iter()
+        .map(Expression::text)
+        .collect::<Vec<String>>();
+    let keywords = class
+        .keywords()
+        .into_iter()
+        .map(|(name, value)| Keyword {
+            name: name.map(str::to_string),
+            value: value.text(),
+        })
+        .collect::<Vec<Keyword>>();
+
+    PythonClass {
+        name: class.name.clone(),
+        methods,
+        inheritance,
+        docstring: class
+            .docstring()
+            .unwrap_or_else(|| MISSING_VALUE.to_string()),
+        attributes: extract_attributes(source, class),
+        keywords,
+        qualified_name: qualified_name.to_string(),
+        nested_classes: class
+            .body
+            .iter()
+            .filter_map(|statement| match statement {
+                Statement::Class(nested) => Some(extract_class(
+                    source,
+                    nested,
+                    &syntax::qualify(qualified_name, &nested.name),
+                )),
+                _ => None,
+            })
+            .collect(),
+        decorators: class.decorators.iter().map(Expression::text).collect(),
+        location: Some(source.location(class.start, class.end)),
+        ..Default::default()
+    }
+}
+
+/// Class attributes assigned in the class body, then instance attributes
+/// assigned on `self` in the methods, starting with `__init__`.
+fn extract_attributes(source: &SourceFile, class: &ClassDef) -> Vec<Attribute> {
+    let mut attributes: Vec<Attribute> = Vec::new();
+    for assignment in class.body.iter().filter_map(Statement::assignment) {
+        for name in assignment.names() {
+            push_attribute(
+                source,
+                &mut attributes,
+                name,
+                &assignment,
+                AttributeScope::Class,
+                None,
+            );
+        }
+    }
+
+    let mut functions = class
+        .body
+        .iter()
+        .filter_map(|statement| match statement {
+            Statement::Function(function) => Some(function),
+            _ => None,
+        })
+        .collect::<Vec<&FunctionDef>>();
+    functions.sort_by_key(|function| function.name != CONSTRUCTOR);
+
+    for function in functions {
+        let method = extract_method(source, function);
+        if method.kind() == MethodKind::Static || method.kind() == MethodKind::Class {
+            continue;
+        }
+        let receiver = match function.parameters.first() {
+            Some(receiver) => receiver.name.clone(),
+            None => continue,
+        };
+        for assignment in function_assignments(&function.body) {
+            for name in assignment.attributes_of(&receiver) {
+                push_attribute(
+                    source,
+                    &mut attributes,
+                    name,
+                    &assignment,
+                    AttributeScope::Instance,
+                    Some(function.name.clone()),
+                );
+            }
+        }
+    }
+
+    attributes
+}
+
+fn push_attribute(
+    source: &SourceFile,
+    attributes: &mut Vec<Attribute>,
+    name: String,
+    assignment: &Assignment,
+    scope: AttributeScope,
+    method: Option<String>,
+) {
+    if attributes.iter().any(|attribute| attribute.name == name) {
+        return;
+    }
+    let default = if assignment.is_unpacking() {
+        None
+    } else {
+        assignment.value.as_ref().map(Expression::text)
+    };
+    attributes.push(Attribute {
+        name,
+        annotation: assignment.annotation.as_ref().map(Expression::text),
+        default,
+        scope,
+        method,
+        location: Some(source.location(assignment.start, assignment.end)),
+        type_source: TypeSource::Implementation,
+    });
+}
+
+/// Assignments made anywhere in a function body, except in nested functions
+/// and classes.
+fn function_assignments(body: &[Statement]) -> Vec<Assignment> {
+    let mut assignments = Vec::new();
+    for statement in body {
+        match statement {
+            Statement::Simple(_) => assignments.extend(statement.assignment()),
+            Statement::Compound { body, .. } => assignments.extend(function_assignments(body)),
+            Statement::Class(_) | Statement::Function(_) => {}
+        }
+    }
+    assignments
+}
+
+/// Extracts a method, with its implicit receiver annotated.
+fn extract_method(source: &SourceFile, function: &FunctionDef) -> Method {
+    extract_function(source, function).annotate_receiver()
+}
+
+fn extract_function(source: &SourceFile, function: &FunctionDef) -> Method {
+    let parameters = function
+        .parameters
+        .iter()
+        .map(|parameter| {
+            Parameter::new(
+                parameter.name.clone(),
+                annotation_text(parameter.annotation.as_ref()),
+            )
+            .with_kind(parameter.kind.clone())
+            .with_default(parameter.default.as_ref().map(Expression::text))
+            .with_location(Some(source.location(parameter.start, parameter.end)))
+        })
+        .collect::<Vec<Parameter>>();
+
+    Method {
+        name: function.name.clone(),
+        parameters,
+        output: annotation_text(function.returns.as_ref()),
+        decorators: function.decorators.iter().map(Expression::text).collect(),
+        is_async: function.is_async,
+        is_generator: function.is_generator(),
+        docstring: function.docstring(),
+        location: Some(source.location(function.start, function.end)),
+        ..Default::default()
+    }
+}
+
+/// Renders the complete annotation expression, as written in the source.
+fn annotation_text(annotation: Option<&Expression>) -> String {
+    match annotation {
+        Some(annotation) => annotation.text(),
+        None => MISSING_VALUE.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        extract_class, load_python_function, load_python_object, load_python_project,
+        syntax::parse_module, SourceFile,
+    };
+    use crate::joneslib::objects::PythonClass;
+    use crate::joneslib::objects::{AttributeScope, DefinitionKind, MethodKind};
+    use crate::joneslib::traversal::Traversal;
+    use std::path::Path;
+
+    fn extract_first_class(source: &str) -> PythonClass {
+        let module = parse_module(source).unwrap();
+        let (qualified_name, class) = module.qualified_classes()[0].clone();
+        extract_class(
+            &SourceFile::new(Path::new("test.py")),
+            class,
+            &qualified_name,
+        )
+    }
+
+    #[test]
+    fn test_extract_full_annotations() {
+        let source = "
+class Annotated:
+    def f(self, a: Dict[str, List[int]], b: \"Foo\", c: int | None,
+          d: typing.Optional[Foo], e: Callable[[int], str]) -> Literal['x']:
+        pass
+";
+        let class = extract_first_class(source);
+        let method = &class.methods[0];
+        let annotations = method
+            .parameters
+            .iter()
+            .map(|parameter| parameter.static_type.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(
+            annotations,
+            vec![
+                "Self",
+                "Dict[str, List[int]]",
+                "\"Foo\"",
+                "int | None",
+                "typing.Optional[Foo]",
+                "Callable[[int], str]",
+            ]
+        );
+        assert_eq!(method.output, "Literal['x']");
+    }
+
+    #[test]
+    fn test_extract_parameter_kinds_and_defaults() {
+        let source = "
+class Signature:
+    def f(self, a: int, /, b: str = 'x', *args, key: Foo = None, flag=False, **kwargs):
+        pass
+
+    def g(self, *, c, d: int = 2):
+        pass
+";
+        let class = extract_first_class(source);
+        assert_eq!(
+            class.methods[0].signature(),
+            "(self, a: int, /, b: str = 'x', *args, key: Foo = None, flag=False, **kwargs)"
+        );
+        assert_eq!(class.methods[1].signature(), "(self, *, c, d: int = 2)");
+        assert_eq!(
+            class.methods[0].parameters[5].default,
+            Some("False".to_string())
+        );
+    }
+
+    #[test]
+    fn test_extract_method_kinds() {
+        let source = "
+class Kinds:
+    @property
+    def size(self) -> int:
+        return 1
+
+    @size.setter
+    def size(self, value: int) -> None:
+        pass
+
+    @staticmethod
+    def make(self):
+        pass
+
+    @classmethod
+    @abc.abstractmethod
+    async def build(cls):
+        pass
+
+    def items(self):
+        def inner():
+            return 1
+        yield inner()
+";
+        let class = extract_first_class(source);
+        let kinds = class
+            .methods
+            .iter()
+            .map(|method| method.kind())
+            .collect::<Vec<MethodKind>>();
+        assert_eq!(
+            kinds,
+            vec![
+                MethodKind::PropertyGetter,
+                MethodKind::PropertySetter,
+                MethodKind::Static,
+                MethodKind::Class,
+                MethodKind::Instance,
+            ]
+        );
+        assert_eq!(class.methods[2].parameters[0].static_type, "None");
+        assert_eq!(class.methods[3].parameters[0].static_type, "Self");
+        assert_eq!(
+            class.methods[3].labels(),
+            vec!["classmethod", "abstract", "async"]
+        );
+        assert!(class.methods[4].is_generator);
+    }
+
+    #[test]
+    fn test_extract_attributes() {
+        let source = "
+class Fields:
+    x: int = 0
+    LIMIT = 10
+    a, b = 1, 2
+
+    def __init__(self, value: int):
+        self.value = value
+        if value:
+            self.flag: bool = True
+        self.x = 3
+
+    def reset(self):
+        self.counter = 0
+
+    @staticmethod
+    def build(other):
+        other.ignored = 1
+";
+        let class = extract_first_class(source);
+        let attributes = class
+            .attributes
+            .iter()
+            .map(|attribute| {
+                (
+                    attribute.name.as_str(),
+                    attribute.default.as_deref(),
+                    attribute.scope,
+                    attribute.method.as_deref(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            attributes,
+            vec![
+                ("x", Some("0"), AttributeScope::Class, None),
+                ("LIMIT", Some("10"), AttributeScope::Class, None),
+                ("a", None, AttributeScope::Class, None),
+                ("b", None, AttributeScope::Class, None),
+                (
+                    "value",
+                    Some("value"),
+                    AttributeScope::Instance,
+                    Some("__init__")
+                ),
+                (
+                    "flag",
+                    Some("True"),
+                    AttributeScope::Instance,
+                    Some("__init__")
+                ),
+                (
+                    "counter",
+                    Some("0"),
+                    AttributeScope::Instance,
+                    Some("reset")
+                ),
+            ]
+        );
+        assert_eq!(class.attributes[0].annotation, Some("int".to_string()));
+    }
+
+    #[test]
+    fn test_load_qualified_names() {
+        let source = "
+class Order:
+    class Meta:
+        ordering = ['id']
+
+class Meta:
+    pass
+
+class Outer:
+    class Inner:
+        pass
+";
+        let test_file = std::env::temp_dir().join("jones_test_load_qualified_names.py");
+        std::fs::write(&test_file, source).unwrap();
+
+        let meta = load_python_object(&test_file, "shop.models", "Meta").unwrap();
+        assert_eq!(meta.qualified_name, "Meta");
+        let order_meta = load_python_object(&test_file, "shop.models", "Order.Meta").unwrap();
+        assert_eq!(order_meta.attributes[0].name, "ordering");
+        let inner = load_python_object(&test_file, "shop.models", "shop.models.Outer.Inner");
+        assert_eq!(inner.unwrap().qualified_name, "Outer.Inner");
+        let outer = load_python_object(&test_file, "shop.models", "models.Outer").unwrap();
+        assert_eq!(outer.nested_classes[0].qualified_name, "Outer.Inner");
+        assert!(load_python_object(&test_file, "shop.models", "other.Outer").is_none());
+
+        std::fs::remove_file(&test_file).unwrap();
+    }
+
+    #[test]
+    fn test_load_module_definitions() {
+        let source = "
+MAX_SIZE = 10
+_DEFAULTS = {}
+timeout: Final[int] = 3
+lower = 1
+A, B = 1, 2
+Color = Enum('Color', 'RED GREEN')
+
+class Helper:
+    LIMIT = 3
+
+def compute(self, x: int) -> int:
+    def inner():
+        pass
+    return x
+";
+        let test_file = std::env::temp_dir().join("jones_test_load_module_definitions.py");
+        std::fs::write(&test_file, source).unwrap();
+
+        let definitions = load_python_project(std::slice::from_ref(&test_file))
+            .unwrap()
+            .definitions
+            .into_iter()
+            .map(|definition| (definition.name, definition.kind))
+            .collect::<Vec<(String, DefinitionKind)>>();
+        assert_eq!(
+            definitions,
+            vec![
+                ("Helper".to_string(), DefinitionKind::Class),
+                ("Color".to_string(), DefinitionKind::Class),
+                ("compute".to_string(), DefinitionKind::Function),
+                ("MAX_SIZE".to_string(), DefinitionKind::Constant),
+                ("_DEFAULTS".to_string(), DefinitionKind::Constant),
+                ("timeout".to_string(), DefinitionKind::Constant),
+            ]
+        );
+        let compute = load_python_function(&test_file, "shop.util", "util.compute").unwrap();
+        assert_eq!(compute.parameters[0].static_type, "None");
+        assert_eq!(compute.output, "int");
+        assert!(load_python_function(&test_file, "shop.util", "inner").is_none());
+
+        std::fs::remove_file(&test_file).unwrap();
+    }
+
+    #[test]
+    fn test_scan_skips_unparsable_files() {
+        let test_dir = std::env::temp_dir().join("jones_test_scan_skips_unparsable_files");
+        std::fs::create_dir_all(&test_dir).unwrap();
+        std::fs::write(
+            test_dir.join("broken.py"),
+            "class Broken:\n    def f(self:\n",
+        )
+        .unwrap();
+        std::fs::write(test_dir.join("good.py"), "class Good:\n    pass\n").unwrap();
+
+        let scan = load_python_project(&Traversal::default().python_files(&test_dir)).unwrap();
+        assert_eq!(scan.definitions[0].name, "Good");
+        assert_eq!(scan.skipped.len(), 1);
+        assert!(scan.skipped[0].file.ends_with("broken.py"));
+        assert!(scan.skipped[0].line.is_some());
+
+        std::fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    #[test]
+    fn test_scan_skips_python2_files() {
+        let test_dir = std::env::temp_dir().join("jones_test_scan_skips_python2_files");
+        std::fs::create_dir_all(&test_dir).unwrap();
+        std::fs::write(
+            test_dir.join("legacy.py"),
+            "class Legacy(object):\n    def run(self):\n        print \"running\"\n",
+        )
+        .unwrap();
+
+        let scan = load_python_project(&Traversal::default().python_files(&test_dir)).unwrap();
+        assert!(scan.definitions.is_empty());
+        assert_eq!(scan.skipped.len(), 1);
+        assert!(scan.skipped[0].file.ends_with("legacy.py"));
+        assert_eq!(scan.skipped[0].line, Some(3));
+        assert!(scan.skipped[0].error.starts_with("Missing parentheses"));
+
+        std::fs::remove_dir_all(&test_dir).unwrap();
+    }
+
+    #[test]
+    fn test_extract_base_expressions_and_keywords() {
+        let source = "
+class Repository(Generic[T], abc.ABC, make_base(), metaclass=ABCMeta, total=False, **extra):
+    pass
+";
+        let class = extract_first_class(source);
+        assert_eq!(
+            class.inheritance,
+            vec!["Generic[T]", "abc.ABC", "make_base()"]
+        );
+        let keywords = class
+            .keywords
+            .iter()
+            .map(|keyword| keyword.to_string())
+            .collect::<Vec<String>>();
+        assert_eq!(
+            keywords,
+            vec!["metaclass=ABCMeta", "total=False", "**extra"]
+        );
+    }
+
+    #[test]
+    fn test_extract_method_docstrings() {
+        let source = "
+class Documented:
+    def move(self, x: int) -> None:
+        \"\"\"Move the piece.
+
+        The piece moves `x` cells forward.
+        \"\"\"
+
+    def stay(self):
+        value = \"not a docstring\"
+
+    def jump(self): 'Jump over.'
+";
+        let class = extract_first_class(source);
+        let docstrings = class
+            .methods
+            .iter()
+            .map(|method| method.docstring.as_deref())
+            .collect::<Vec<Option<&str>>>();
+        assert_eq!(
+            docstrings,
+            vec![
+                Some("Move the piece.\n\nThe piece moves `x` cells forward."),
+                None,
+                Some("Jump over."),
+            ]
+        );
+        assert_eq!(
+            class.methods[0].docstring_summary(),
+            Some("Move the piece.")
+        );
+    }
+
+    #[test]
+    fn test_extract_locations() {
+        let source = "
+class Located:
+    size: int = 3
+
+    @property
+    def area(self,
+             scale: float = 1.0) -> int:
+        return self.size
+";
+        let class = extract_first_class(source);
+        let location = class.location.unwrap();
+        assert_eq!(location.to_string(), "test.py:2");
+        assert_eq!((location.end_line, location.end_column), (8, 24));
+
+        let method = &class.methods[0];
+        let method_location = method.location.as_ref().unwrap();
+        assert_eq!((method_location.line, method_location.column), (6, 4));
+        let scale = method.parameters[1].location.as_ref().unwrap();
+        assert_eq!((scale.line, scale.column, scale.end_column), (7, 13, 25));
+        assert_eq!(class.attributes[0].location.as_ref().unwrap().line, 3);
+    }
+}
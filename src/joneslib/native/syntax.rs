@@ -0,0 +1,1271 @@
+/*
+JonesCLI
+
+Author: Vlad Nedelcu
+Date: Jul 2021
+License: MIT
+
+Copyright 2021 Vlad Nedelcu
+*/
+use super::lexer::{tokenize, tokenize_partial, Position, SyntaxError, Token, TokenKind};
+use crate::joneslib::objects::ParameterKind;
+
+/// An expression kept as the tokens it was written with. Jones never needs
+/// to evaluate Python, only to show it back, so the tokens are enough.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression {
+    pub tokens: Vec<Token>,
+}
+impl Expression {
+    /// Renders the expression on a single line, dropping comments and the
+    /// line breaks used inside brackets.
+    pub fn text(&self) -> String {
+        render_tokens(&self.tokens)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParameterDef {
+    pub name: String,
+    pub kind: ParameterKind,
+    pub annotation: Option<Expression>,
+    pub default: Option<Expression>,
+    /// Span of the name and annotation, like the `ast.arg` node.
+    pub start: Position,
+    pub end: Position,
+}
+
+/// An argument of a class definition or a call: a positional value such as
+/// a base (`Base[int]`), a keyword (`metaclass=ABCMeta`) or an unpacked
+/// mapping (`**options`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Argument {
+    Positional(Expression),
+    Keyword(String, Expression),
+    Unpacked(Expression),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassDef {
+    pub name: String,
+    pub arguments: Vec<Argument>,
+    pub decorators: Vec<Expression>,
+    pub body: Vec<Statement>,
+    pub start: Position,
+    pub end: Position,
+}
+impl ClassDef {
+    pub fn bases(&self) -> Vec<&Expression> {
+        self.arguments
+            .iter()
+            .filter_map(|argument| match argument {
+                Argument::Positional(value) => Some(value),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Class keywords, `None` standing for an unpacked `**mapping`.
+    pub fn keywords(&self) -> Vec<(Option<&str>, &Expression)> {
+        self.arguments
+            .iter()
+            .filter_map(|argument| match argument {
+                Argument::Keyword(name, value) => Some((Some(name.as_str()), value)),
+                Argument::Unpacked(value) => Some((None, value)),
+                Argument::Positional(_) => None,
+            })
+            .collect()
+    }
+
+    pub fn docstring(&self) -> Option<String> {
+        docstring(&self.body)
+    }
+}
+
+/// A call of a dotted name, such as `field(default_factory=list)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Call {
+    pub callee: String,
+    pub arguments: Vec<Argument>,
+}
+impl Call {
+    /// The callee without its module path, `dataclasses.field` gives `field`.
+    pub fn name(&self) -> &str {
+        self.callee.rsplit('.').next().unwrap_or(&self.callee)
+    }
+
+    pub fn positional(&self) -> Vec<String> {
+        self.arguments
+            .iter()
+            .filter_map(|argument| match argument {
+                Argument::Positional(value) => Some(value.text()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn keyword(&self, name: &str) -> Option<String> {
+        self.arguments.iter().find_map(|argument| match argument {
+            Argument::Keyword(keyword, value) if keyword == name => Some(value.text()),
+            _ => None,
+        })
+    }
+
+    pub fn keywords(&self) -> Vec<(&str, String)> {
+        self.arguments
+            .iter()
+            .filter_map(|argument| match argument {
+                Argument::Keyword(keyword, value) => Some((keyword.as_str(), value.text())),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionDef {
+    pub name: String,
+    pub is_async: bool,
+    pub parameters: Vec<ParameterDef>,
+    pub returns: Option<Expression>,
+    pub decorators: Vec<Expression>,
+    pub body: Vec<Statement>,
+    pub start: Position,
+    pub end: Position,
+}
+impl FunctionDef {
+    /// A function is a generator when a `yield` appears in its own body,
+    /// outside of nested functions and classes.
+    pub fn is_generator(&self) -> bool {
+        contains_yield(&self.body)
+    }
+
+    pub fn docstring(&self) -> Option<String> {
+        docstring(&self.body)
+    }
+}
+
+fn contains_yield(body: &[Statement]) -> bool {
+    body.iter().any(|statement| match statement {
+        Statement::Simple(tokens) => tokens.iter().any(|token| token.is_name("yield")),
+        Statement::Compound { header, body } => {
+            header.iter().any(|token| token.is_name("yield")) || contains_yield(body)
+        }
+        Statement::Class(_) | Statement::Function(_) => false,
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    Class(ClassDef),
+    Function(FunctionDef),
+    /// Any other block statement (`if`, `try`, `with`...), kept so that
+    /// definitions nested in it can still be found.
+    Compound {
+        header: Vec<Token>,
+        body: Vec<Statement>,
+    },
+    Simple(Vec<Token>),
+}
+
+/// An assignment statement, plain (`a = b = 1`) or annotated (`a: int = 1`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assignment {
+    pub targets: Vec<Expression>,
+    pub annotation: Option<Expression>,
+    pub value: Option<Expression>,
+    pub start: Position,
+    pub end: Position,
+}
+impl Assignment {
+    /// Names bound by the assignment, unpacking tuple targets such as `a, b`.
+    pub fn names(&self) -> Vec<String> {
+        self.targets
+            .iter()
+            .flat_map(|target| unpack_target(&target.tokens))
+            .filter(|tokens| tokens.len() == 1 && tokens[0].kind == TokenKind::Name)
+            .map(|tokens| tokens[0].text.clone())
+            .collect()
+    }
+
+    /// Attributes set on `receiver` by the assignment, e.g. `x` for `self.x = 1`.
+    pub fn attributes_of(&self, receiver: &str) -> Vec<String> {
+        self.targets
+            .iter()
+            .flat_map(|target| unpack_target(&target.tokens))
+            .filter(|tokens| {
+                tokens.len() == 3
+                    && tokens[0].is_name(receiver)
+                    && tokens[1].is_operator(".")
+                    && tokens[2].kind == TokenKind::Name
+            })
+            .map(|tokens| tokens[2].text.clone())
+            .collect()
+    }
+
+    /// Whether the value can be shown next to every target, which is not the
+    /// case when it gets unpacked.
+    pub fn is_unpacking(&self) -> bool {
+        self.targets
+            .iter()
+            .any(|target| unpack_target(&target.tokens).len() > 1)
+    }
+}
+
+fn unpack_target(tokens: &[Token]) -> Vec<Vec<Token>> {
+    let inner = match (tokens.first(), tokens.last()) {
+        (Some(first), Some(last))
+            if tokens.len() > 1
+                && ((first.is_operator("(") && last.is_operator(")"))
+                    || (first.is_operator("[") && last.is_operator("]"))) =>
+        {
+            &tokens[1..tokens.len() - 1]
+        }
+        _ => tokens,
+    };
+    split_top_level(inner, ",")
+        .into_iter()
+        .map(|element| match element.first() {
+            Some(first) if first.is_operator("*") => element[1..].to_vec(),
+            _ => element,
+        })
+        .collect()
+}
+
+impl Statement {
+    pub fn assignment(&self) -> Option<Assignment> {
+        let tokens = match self {
+            Statement::Simple(tokens) => tokens,
+            _ => return None,
+        };
+        let start = tokens.first()?.start;
+        let end = tokens.last()?.end;
+        let colon = top_level_position(tokens, ":");
+        let equal = top_level_position(tokens, "=");
+
+        if let Some(colon) = colon {
+            if equal.is_none_or(|equal| colon < equal) {
+                let annotation = match equal {
+                    Some(equal) => &tokens[colon + 1..equal],
+                    None => &tokens[colon + 1..],
+                };
+                return Some(Assignment {
+                    targets: vec![Expression {
+                        tokens: tokens[..colon].to_vec(),
+                    }],
+                    annotation: Some(Expression {
+                        tokens: annotation.to_vec(),
+                    }),
+                    value: equal.map(|equal| Expression {
+                        tokens: tokens[equal + 1..].to_vec(),
+                    }),
+                    start,
+                    end,
+                });
+            }
+        }
+
+        equal?;
+        let mut parts = split_top_level(tokens, "=")
+            .into_iter()
+            .map(|tokens| Expression { tokens })
+            .collect::<Vec<Expression>>();
+        if parts.len() < 2 {
+            return None;
+        }
+        let value = parts.pop();
+        Some(Assignment {
+            targets: parts,
+            annotation: None,
+            value,
+            start,
+            end,
+        })
+    }
+}
+
+fn top_level_position(tokens: &[Token], operator: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (index, token) in tokens.iter().enumerate() {
+        if token.is_opening_bracket() {
+            depth += 1;
+        } else if token.is_closing_bracket() {
+            depth -= 1;
+        } else if depth == 0 && token.is_operator(operator) {
+            return Some(index);
+        }
+    }
+    None
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Module {
+    pub body: Vec<Statement>,
+}
+impl Module {
+    /// Every class of the module in the order `ast.walk` would yield them:
+    /// breadth first, including classes nested in functions and blocks. Each
+    /// class comes with its `__qualname__`, such as `Outer.Inner` or
+    /// `factory.<locals>.Local`.
+    pub fn qualified_classes(&self) -> Vec<(String, &ClassDef)> {
+        let mut found = Vec::new();
+        let mut queue = self
+            .body
+            .iter()
+            .map(|statement| (String::new(), statement))
+            .collect::<std::collections::VecDeque<(String, &Statement)>>();
+        while let Some((scope, statement)) = queue.pop_front() {
+            match statement {
+                Statement::Class(class) => {
+                    let qualified_name = qualify(&scope, &class.name);
+                    queue.extend(
+                        class
+                            .body
+                            .iter()
+                            .map(|child| (qualified_name.clone(), child)),
+                    );
+                    found.push((qualified_name, class));
+                }
+                Statement::Function(function) => {
+                    let function_scope = format!("{}.<locals>", qualify(&scope, &function.name));
+                    queue.extend(
+                        function
+                            .body
+                            .iter()
+                            .map(|child| (function_scope.clone(), child)),
+                    );
+                }
+                Statement::Compound { body, .. } => {
+                    queue.extend(body.iter().map(|child| (scope.clone(), child)))
+                }
+                Statement::Simple(_) => {}
+            }
+        }
+        found
+    }
+}
+
+pub fn qualify(scope: &str, name: &str) -> String {
+    if scope.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", scope, name)
+    }
+}
+
+/// Words that may follow a bare `print` or `exec` name in Python 3 code.
+const EXPRESSION_CONTINUATIONS: [&str; 8] = ["if", "else", "in", "is", "and", "or", "for", "not"];
+
+/// Parses a Python module into the definitions jones cares about. Like
+/// CPython, the error reported is the first one in the file, whether the
+/// tokenizer or the parser finds it.
+pub fn parse_module(source: &str) -> Result<Module, SyntaxError> {
+    let (tokens, lexer_error) = tokenize_partial(source);
+    let mut parser = Parser { tokens, cursor: 0 };
+    let parsed = parser.parse_block();
+    match (parsed, lexer_error) {
+        (Err(error), Some(lexer_error))
+            if (error.position.line, error.position.column)
+                < (lexer_error.position.line, lexer_error.position.column) =>
+        {
+            Err(error)
+        }
+        (_, Some(lexer_error)) => Err(lexer_error),
+        (parsed, None) => parsed.map(|body| Module { body }),
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    cursor: usize,
+}
+
+impl Parser {
+    fn current(&self) -> &Token {
+        &self.tokens[self.cursor.min(self.tokens.len() - 1)]
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.current().clone();
+        if self.cursor < self.tokens.len() - 1 {
+            self.cursor += 1;
+        }
+        token
+    }
+
+    fn error(&self, message: &str) -> SyntaxError {
+        SyntaxError {
+            message: message.to_string(),
+            position: self.current().start,
+        }
+    }
+
+    fn expect_operator(&mut self, value: &str) -> Result<Token, SyntaxError> {
+        if self.current().is_operator(value) {
+            Ok(self.advance())
+        } else {
+            Err(self.error(&format!("expected '{}'", value)))
+        }
+    }
+
+    fn expect_name(&mut self) -> Result<Token, SyntaxError> {
+        if self.current().kind == TokenKind::Name {
+            Ok(self.advance())
+        } else {
+            Err(self.error("expected a name"))
+        }
+    }
+
+    /// End of the last consumed token that is part of the source text,
+    /// skipping the NEWLINE/INDENT/DEDENT markers.
+    fn previous_end(&self) -> Position {
+        self.tokens[..self.cursor]
+            .iter()
+            .rev()
+            .find(|token| {
+                !matches!(
+                    token.kind,
+                    TokenKind::Newline | TokenKind::Indent | TokenKind::Dedent
+                )
+            })
+            .map(|token| token.end)
+            .unwrap_or_default()
+    }
+
+    /// Parses statements until the end of the current block.
+    fn parse_block(&mut self) -> Result<Vec<Statement>, SyntaxError> {
+        let mut statements = Vec::new();
+        loop {
+            match self.current().kind {
+                TokenKind::Dedent => {
+                    self.advance();
+                    break;
+                }
+                TokenKind::EndOfFile => break,
+                TokenKind::Newline => {
+                    self.advance();
+                }
+                TokenKind::Indent => return Err(self.error("unexpected indent")),
+                _ => statements.extend(self.parse_statement()?),
+            }
+        }
+        Ok(statements)
+    }
+
+    fn parse_statement(&mut self) -> Result<Vec<Statement>, SyntaxError> {
+        let mut decorators = Vec::new();
+        while self.current().is_operator("@") {
+            self.advance();
+            decorators.push(Expression {
+                tokens: self.take_line(),
+            });
+            self.skip_newlines();
+        }
+
+        let token = self.current().clone();
+        if token.is_name("class") {
+            return Ok(vec![Statement::Class(self.parse_class(decorators)?)]);
+        }
+        if token.is_name("def") || (token.is_name("async") && self.peek_is_name(1, "def")) {
+            return Ok(vec![Statement::Function(self.parse_function(decorators)?)]);
+        }
+        if !decorators.is_empty() {
+            return Err(self.error("expected a class or function after decorator"));
+        }
+
+        let line = self.take_line();
+        check_except_clause(&line)?;
+        if self.current().kind == TokenKind::Indent {
+            self.advance();
+            let body = self.parse_block()?;
+            return Ok(vec![Statement::Compound { header: line, body }]);
+        }
+        simple_statements(line)
+    }
+
+    fn peek_is_name(&self, skip: usize, value: &str) -> bool {
+        self.tokens
+            .get(self.cursor + skip)
+            .is_some_and(|token| token.is_name(value))
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.current().kind == TokenKind::Newline {
+            self.advance();
+        }
+    }
+
+    /// Consumes the tokens of the current logical line, and its NEWLINE.
+    fn take_line(&mut self) -> Vec<Token> {
+        let mut line = Vec::new();
+        while !matches!(
+            self.current().kind,
+            TokenKind::Newline | TokenKind::EndOfFile | TokenKind::Indent | TokenKind::Dedent
+        ) {
+            line.push(self.advance());
+        }
+        if self.current().kind == TokenKind::Newline {
+            self.advance();
+        }
+        line
+    }
+
+    /// Consumes tokens until the matching closing bracket of the one just consumed.
+    fn take_bracketed(&mut self) -> Result<Vec<Token>, SyntaxError> {
+        let mut depth = 1;
+        let mut inner = Vec::new();
+        loop {
+            let token = self.current().clone();
+            if token.kind == TokenKind::EndOfFile {
+                return Err(self.error("unexpected end of file"));
+            }
+            if token.is_opening_bracket() {
+                depth += 1;
+            } else if token.is_closing_bracket() {
+                depth -= 1;
+                if depth == 0 {
+                    self.advance();
+                    return Ok(inner);
+                }
+            }
+            inner.push(self.advance());
+        }
+    }
+
+    /// Parses what follows the `:` of a class or function header.
+    fn parse_suite(&mut self) -> Result<Vec<Statement>, SyntaxError> {
+        if self.current().kind == TokenKind::Newline {
+            self.advance();
+            if self.current().kind != TokenKind::Indent {
+                return Err(self.error("expected an indented block"));
+            }
+            self.advance();
+            return self.parse_block();
+        }
+        simple_statements(self.take_line())
+    }
+
+    fn parse_class(&mut self, decorators: Vec<Expression>) -> Result<ClassDef, SyntaxError> {
+        let start = self.current().start;
+        self.advance();
+        let name = self.expect_name()?.text;
+
+        let mut arguments = Vec::new();
+        if self.current().is_operator("(") {
+            self.advance();
+            for tokens in split_top_level(&self.take_bracketed()?, ",") {
+                arguments.push(parse_argument(tokens));
+            }
+        }
+        self.expect_operator(":")?;
+        let body = self.parse_suite()?;
+
+        Ok(ClassDef {
+            name,
+            arguments,
+            decorators,
+            body,
+            start,
+            end: self.previous_end(),
+        })
+    }
+
+    fn parse_function(&mut self, decorators: Vec<Expression>) -> Result<FunctionDef, SyntaxError> {
+        let start = self.current().start;
+        let is_async = self.current().is_name("async");
+        if is_async {
+            self.advance();
+        }
+        self.advance();
+        let name = self.expect_name()?.text;
+
+        self.expect_operator("(")?;
+        let parameters = parse_parameters(&self.take_bracketed()?)?;
+
+        let mut returns = None;
+        if self.current().is_operator("->") {
+            self.advance();
+            let mut tokens = Vec::new();
+            let mut depth = 0;
+            loop {
+                let token = self.current();
+                if token.is_operator(":") && depth == 0 {
+                    break;
+                }
+                if matches!(token.kind, TokenKind::Newline | TokenKind::EndOfFile) {
+                    return Err(self.error("expected ':'"));
+                }
+                if token.is_opening_bracket() {
+                    depth += 1;
+                } else if token.is_closing_bracket() {
+                    depth -= 1;
+                }
+                tokens.push(self.advance());
+            }
+            returns = Some(Expression { tokens });
+        }
+        self.expect_operator(":")?;
+        let body = self.parse_suite()?;
+
+        Ok(FunctionDef {
+            name,
+            is_async,
+            parameters,
+            returns,
+            decorators,
+            body,
+            start,
+            end: self.previous_end(),
+        })
+    }
+}
+
+fn parse_argument(tokens: Vec<Token>) -> Argument {
+    let is_keyword =
+        tokens.len() > 2 && tokens[0].kind == TokenKind::Name && tokens[1].is_operator("=");
+    if is_keyword {
+        Argument::Keyword(
+            tokens[0].text.clone(),
+            Expression {
+                tokens: tokens[2..].to_vec(),
+            },
+        )
+    } else if tokens.first().is_some_and(|token| token.is_operator("**")) {
+        Argument::Unpacked(Expression {
+            tokens: tokens[1..].to_vec(),
+        })
+    } else {
+        Argument::Positional(Expression { tokens })
+    }
+}
+
+fn parse_parameters(tokens: &[Token]) -> Result<Vec<ParameterDef>, SyntaxError> {
+    let groups = split_top_level(tokens, ",");
+    let has_positional_only = groups
+        .iter()
+        .any(|group| group.len() == 1 && group[0].is_operator("/"));
+
+    let mut parameters = Vec::new();
+    let mut kind = if has_positional_only {
+        ParameterKind::PositionalOnly
+    } else {
+        ParameterKind::Regular
+    };
+    for group in groups {
+        let first = &group[0];
+        if group.len() == 1 && first.is_operator("/") {
+            kind = ParameterKind::Regular;
+            continue;
+        }
+        if group.len() == 1 && first.is_operator("*") {
+            kind = ParameterKind::KeywordOnly;
+            continue;
+        }
+
+        let (parameter_kind, rest) = if first.is_operator("*") {
+            kind = ParameterKind::KeywordOnly;
+            (ParameterKind::VarPositional, &group[1..])
+        } else if first.is_operator("**") {
+            (ParameterKind::VarKeyword, &group[1..])
+        } else {
+            (kind.clone(), &group[..])
+        };
+
+        let name = match rest.first() {
+            Some(token) if token.kind == TokenKind::Name => token.clone(),
+            _ => {
+                return Err(SyntaxError {
+                    message: "invalid parameter".to_string(),
+                    position: first.start,
+                })
+            }
+        };
+        let rest = &rest[1..];
+        let annotation_start = rest.iter().position(|token| token.is_operator(":"));
+        let default_start = rest.iter().position(|token| token.is_operator("="));
+        let annotation = match (annotation_start, default_start) {
+            (Some(colon), Some(equal)) if colon < equal => Some(rest[colon + 1..equal].to_vec()),
+            (Some(colon), None) => Some(rest[colon + 1..].to_vec()),
+            _ => None,
+        };
+        let default = default_start.map(|equal| rest[equal + 1..].to_vec());
+
+        let end = annotation
+            .as_ref()
+            .and_then(|tokens| tokens.last())
+            .unwrap_or(&name)
+            .end;
+        parameters.push(ParameterDef {
+            name: name.text.clone(),
+            kind: parameter_kind,
+            annotation: annotation.map(|tokens| Expression { tokens }),
+            default: default.map(|tokens| Expression { tokens }),
+            start: name.start,
+            end,
+        });
+    }
+    Ok(parameters)
+}
+
+fn expression_tokens(expression: &str) -> Option<Vec<Token>> {
+    Some(
+        tokenize(expression)
+            .ok()?
+            .into_iter()
+            .filter(|token| {
+                !matches!(
+                    token.kind,
+                    TokenKind::Newline
+                        | TokenKind::Indent
+                        | TokenKind::Dedent
+                        | TokenKind::EndOfFile
+                )
+            })
+            .collect(),
+    )
+}
+
+/// Parses a dict display whose keys are plain strings, such as
+/// `{'extra': 'forbid'}`, into its (key, value) pairs.
+pub fn parse_dict(expression: &str) -> Option<Vec<(String, String)>> {
+    let tokens = expression_tokens(expression)?;
+    if !tokens.first()?.is_operator("{") || !tokens.last()?.is_operator("}") || tokens.len() < 2 {
+        return None;
+    }
+    split_top_level(&tokens[1..tokens.len() - 1], ",")
+        .into_iter()
+        .map(|entry| {
+            let colon = entry.iter().position(|token| token.is_operator(":"))?;
+            let key = string_value(&entry[..colon])?;
+            Some((key, render_tokens(&entry[colon + 1..])))
+        })
+        .collect()
+}
+
+/// Parses a list or tuple display, such as `['RED', ('GREEN', 2)]`, into its
+/// elements.
+pub fn parse_sequence(expression: &str) -> Option<Vec<String>> {
+    let tokens = expression_tokens(expression)?;
+    let (first, last) = (tokens.first()?, tokens.last()?);
+    let is_display = (first.is_operator("[") && last.is_operator("]"))
+        || (first.is_operator("(") && last.is_operator(")"));
+    if !is_display || tokens.len() < 2 || !is_single_group(&tokens) {
+        return None;
+    }
+    Some(
+        split_top_level(&tokens[1..tokens.len() - 1], ",")
+            .iter()
+            .map(|element| render_tokens(element))
+            .collect(),
+    )
+}
+
+/// Whether the first bracket of the tokens is only closed by the last one.
+fn is_single_group(tokens: &[Token]) -> bool {
+    let mut depth = 0;
+    for (position, token) in tokens.iter().enumerate() {
+        if token.is_opening_bracket() {
+            depth += 1;
+        } else if token.is_closing_bracket() {
+            depth -= 1;
+            if depth == 0 && position != tokens.len() - 1 {
+                return false;
+            }
+        }
+    }
+    depth == 0
+}
+
+/// Parses an expression made of a single call, like the rendered default
+/// `dataclasses.field(default=0)`. Anything else gives `None`.
+pub fn parse_call(expression: &str) -> Option<Call> {
+    let tokens = expression_tokens(expression)?;
+
+    let mut callee = Vec::new();
+    let mut index = 0;
+    while tokens.get(index)?.kind == TokenKind::Name {
+        callee.push(tokens[index].text.clone());
+        index += 1;
+        if !tokens.get(index)?.is_operator(".") {
+            break;
+        }
+        index += 1;
+    }
+    if callee.is_empty()
+        || !tokens.get(index)?.is_operator("(")
+        || !is_single_group(&tokens[index..])
+    {
+        return None;
+    }
+
+    Some(Call {
+        callee: callee.join("."),
+        arguments: split_top_level(&tokens[index + 1..tokens.len() - 1], ",")
+            .into_iter()
+            .map(parse_argument)
+            .collect(),
+    })
+}
+
+/// Splits tokens on a separator that is not nested in brackets, dropping
+/// empty groups such as the one left by a trailing comma.
+pub fn split_top_level(tokens: &[Token], separator: &str) -> Vec<Vec<Token>> {
+    let mut groups = Vec::new();
+    let mut current = Vec::new();
+    let mut depth = 0;
+    for token in tokens {
+        if token.is_opening_bracket() {
+            depth += 1;
+        } else if token.is_closing_bracket() {
+            depth -= 1;
+        }
+        if depth == 0 && token.is_operator(separator) {
+            groups.push(std::mem::take(&mut current));
+            continue;
+        }
+        current.push(token.clone());
+    }
+    groups.push(current);
+    groups
+        .into_iter()
+        .filter(|group| !group.is_empty())
+        .collect()
+}
+
+fn simple_statements(line: Vec<Token>) -> Result<Vec<Statement>, SyntaxError> {
+    let statements = split_top_level(&line, ";");
+    for statement in &statements {
+        check_print_statement(statement)?;
+    }
+    Ok(statements.into_iter().map(Statement::Simple).collect())
+}
+
+/// Rejects the Python 2 `print x` and `exec code` statements.
+fn check_print_statement(tokens: &[Token]) -> Result<(), SyntaxError> {
+    let keyword = match tokens.first() {
+        Some(token) if token.is_name("print") || token.is_name("exec") => token,
+        _ => return Ok(()),
+    };
+    let statement = match tokens.get(1) {
+        Some(token) if token.kind == TokenKind::Name => {
+            !EXPRESSION_CONTINUATIONS.contains(&token.text.as_str())
+                || (token.text == "not" && !tokens.get(2).is_some_and(|next| next.is_name("in")))
+        }
+        Some(token) => {
+            matches!(token.kind, TokenKind::Number | TokenKind::String) || token.is_operator("{")
+        }
+        None => false,
+    };
+    if !statement {
+        return Ok(());
+    }
+    Err(SyntaxError {
+        message: format!(
+            "Missing parentheses in call to '{0}'. Did you mean {0}(...)?",
+            keyword.text
+        ),
+        position: keyword.start,
+    })
+}
+
+/// Rejects the Python 2 `except Error, name:` clause.
+fn check_except_clause(line: &[Token]) -> Result<(), SyntaxError> {
+    if !line.first().is_some_and(|token| token.is_name("except")) {
+        return Ok(());
+    }
+    let header = match top_level_position(line, ":") {
+        Some(colon) => &line[1..colon],
+        None => &line[1..],
+    };
+    if top_level_position(header, ",").is_none() {
+        return Ok(());
+    }
+    Err(SyntaxError {
+        message: "multiple exception types must be parenthesized".to_string(),
+        position: header[0].start,
+    })
+}
+
+/// Joins tokens back into source text on a single line.
+pub fn render_tokens(tokens: &[Token]) -> String {
+    let mut text = String::new();
+    for (index, token) in tokens.iter().enumerate() {
+        if index > 0 {
+            let previous = &tokens[index - 1];
+            let same_line = previous.end.line == token.start.line;
+            let separated = token.offset > previous.end_offset;
+            let tight = previous.is_opening_bracket()
+                || token.is_closing_bracket()
+                || token.is_operator(",");
+            if separated && (same_line || !tight) {
+                text.push(' ');
+            }
+        }
+        text.push_str(&token.text);
+    }
+    text
+}
+
+fn docstring(body: &[Statement]) -> Option<String> {
+    match body.first() {
+        Some(Statement::Simple(tokens)) => string_value(tokens),
+        _ => None,
+    }
+}
+
+/// Evaluates a statement made of (implicitly concatenated) plain string
+/// literals, the only form Python accepts as a docstring.
+pub fn string_value(tokens: &[Token]) -> Option<String> {
+    if tokens.is_empty() || tokens.iter().any(|token| token.kind != TokenKind::String) {
+        return None;
+    }
+    let mut value = String::new();
+    for token in tokens {
+        value.push_str(&literal_value(&token.text)?);
+    }
+    Some(clean_docstring(&value))
+}
+
+/// Decodes one string literal. Byte strings and f-strings are not plain
+/// strings, so they yield nothing, like any other expression.
+pub fn literal_value(literal: &str) -> Option<String> {
+    let prefix_length = literal.find(['\'', '"'])?;
+    let prefix = literal[..prefix_length].to_ascii_lowercase();
+    if prefix.len() > 2
+        || !prefix
+            .chars()
+            .all(|character| matches!(character, 'r' | 'u'))
+    {
+        return None;
+    }
+    let body = &literal[prefix_length..];
+    let quote_length = if body.starts_with("\"\"\"") || body.starts_with("'''") {
+        3
+    } else {
+        1
+    };
+    if body.len() < quote_length * 2 || !body.ends_with(&body[..quote_length]) {
+        return None;
+    }
+    let inner = &body[quote_length..body.len() - quote_length];
+    if prefix.contains('r') {
+        return Some(inner.to_string());
+    }
+    Some(unescape(inner))
+}
+
+fn unescape(value: &str) -> String {
+    let mut result = String::new();
+    let mut characters = value.chars().peekable();
+    while let Some(character) = characters.next() {
+        if character != '\\' {
+            result.push(character);
+            continue;
+        }
+        match characters.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some('0') => result.push('\0'),
+            Some('\\') => result.push('\\'),
+            Some('\'') => result.push('\''),
+            Some('"') => result.push('"'),
+            Some('\n') => {}
+            Some(other) => {
+                result.push('\\');
+                result.push(other);
+            }
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+/// Same as Python's `inspect.cleandoc`, which `ast.get_docstring` applies.
+pub fn clean_docstring(docstring: &str) -> String {
+    let expanded = docstring.replace('\t', "        ");
+    let lines = expanded.lines().collect::<Vec<&str>>();
+    let margin = lines
+        .iter()
+        .skip(1)
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+
+    let mut cleaned = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        if index == 0 {
+            cleaned.push(line.trim_start().to_string());
+        } else if line.len() >= margin {
+            cleaned.push(line[margin..].trim_end().to_string());
+        } else {
+            cleaned.push(line.trim_end().to_string());
+        }
+    }
+    while cleaned.first().is_some_and(|line| line.is_empty()) {
+        cleaned.remove(0);
+    }
+    while cleaned.last().is_some_and(|line| line.is_empty()) {
+        cleaned.pop();
+    }
+    cleaned.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        literal_value, parse_call, parse_dict, parse_module, parse_sequence, ParameterKind,
+        Position, Statement,
+    };
+
+    static PYTHON_CODE: &str = r#"
+import os
+
+@decorate(with_args=True)
+class Board(Tool, abc.Base, metaclass=ABCMeta):
+    """Tic Tac Toe board
+
+        with an indented line
+    """
+    size: int = 3
+
+    def __init__(self, name: str, /, rows: List[int] = [1, 2], *args, key: "Foo" = None, **kwargs) -> None:
+        self.name = name
+
+    async def play(self) -> Dict[str,
+                                 int]:  # comment
+        pass
+
+    class Inner: pass
+
+if True:
+    class Hidden(object):
+        def method(self): return 1
+"#;
+
+    #[test]
+    fn test_parse_module_classes() {
+        let module = parse_module(PYTHON_CODE).unwrap();
+        let names = module
+            .qualified_classes()
+            .into_iter()
+            .map(|(_, class)| class.name.clone())
+            .collect::<Vec<String>>();
+        assert_eq!(names, vec!["Board", "Inner", "Hidden"]);
+    }
+
+    #[test]
+    fn test_parse_qualified_names() {
+        let module = parse_module(
+            "class Outer:\n    class Inner:\n        class Deep: pass\ndef factory():\n    class Local: pass\n",
+        )
+        .unwrap();
+        let names = module
+            .qualified_classes()
+            .into_iter()
+            .map(|(qualified_name, _)| qualified_name)
+            .collect::<Vec<String>>();
+        assert_eq!(
+            names,
+            vec![
+                "Outer",
+                "Outer.Inner",
+                "factory.<locals>.Local",
+                "Outer.Inner.Deep"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_class_header() {
+        let module = parse_module(PYTHON_CODE).unwrap();
+        let board = module.qualified_classes()[0].1;
+        let bases = board
+            .bases()
+            .iter()
+            .map(|base| base.text())
+            .collect::<Vec<String>>();
+        assert_eq!(bases, vec!["Tool", "abc.Base"]);
+        let keywords = board
+            .keywords()
+            .into_iter()
+            .map(|(name, value)| (name, value.text()))
+            .collect::<Vec<_>>();
+        assert_eq!(keywords, vec![(Some("metaclass"), "ABCMeta".to_string())]);
+        assert_eq!(board.decorators[0].text(), "decorate(with_args=True)");
+        assert_eq!(
+            board.docstring(),
+            Some("Tic Tac Toe board\n\nwith an indented line".to_string())
+        );
+        assert_eq!(board.start, Position { line: 5, column: 0 });
+        assert_eq!(
+            board.end,
+            Position {
+                line: 19,
+                column: 21
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_function_signature() {
+        let module = parse_module(PYTHON_CODE).unwrap();
+        let functions = module.qualified_classes()[0]
+            .1
+            .body
+            .iter()
+            .filter_map(|statement| match statement {
+                Statement::Function(function) => Some(function),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let init = functions[0];
+        let kinds = init
+            .parameters
+            .iter()
+            .map(|parameter| (parameter.name.as_str(), parameter.kind.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                ("self", ParameterKind::PositionalOnly),
+                ("name", ParameterKind::PositionalOnly),
+                ("rows", ParameterKind::Regular),
+                ("args", ParameterKind::VarPositional),
+                ("key", ParameterKind::KeywordOnly),
+                ("kwargs", ParameterKind::VarKeyword),
+            ]
+        );
+        assert_eq!(
+            init.parameters[2].default.as_ref().unwrap().text(),
+            "[1, 2]"
+        );
+        assert_eq!(
+            init.parameters[4].annotation.as_ref().unwrap().text(),
+            "\"Foo\""
+        );
+
+        let play = functions[1];
+        assert!(play.is_async);
+        assert_eq!(play.returns.as_ref().unwrap().text(), "Dict[str, int]");
+    }
+
+    #[test]
+    fn test_parse_assignments() {
+        let source =
+            "a: int = 1\nb = c = [1, 2]\nd, *e = f\nself.x: str\nfn = lambda y: y\nprint(k=1)\n";
+        let module = parse_module(source).unwrap();
+        let assignments = module
+            .body
+            .iter()
+            .map(|statement| statement.assignment())
+            .collect::<Vec<_>>();
+
+        let annotated = assignments[0].as_ref().unwrap();
+        assert_eq!(annotated.names(), vec!["a"]);
+        assert_eq!(annotated.annotation.as_ref().unwrap().text(), "int");
+        assert_eq!(annotated.value.as_ref().unwrap().text(), "1");
+
+        let chained = assignments[1].as_ref().unwrap();
+        assert_eq!(chained.names(), vec!["b", "c"]);
+        assert_eq!(chained.value.as_ref().unwrap().text(), "[1, 2]");
+
+        let unpacked = assignments[2].as_ref().unwrap();
+        assert_eq!(unpacked.names(), vec!["d", "e"]);
+        assert!(unpacked.is_unpacking());
+
+        let attribute = assignments[3].as_ref().unwrap();
+        assert_eq!(attribute.attributes_of("self"), vec!["x"]);
+        assert!(attribute.value.is_none());
+
+        let lambda = assignments[4].as_ref().unwrap();
+        assert!(lambda.annotation.is_none());
+        assert_eq!(lambda.value.as_ref().unwrap().text(), "lambda y: y");
+
+        assert!(assignments[5].is_none());
+    }
+
+    #[test]
+    fn test_parse_invalid_module() {
+        assert!(parse_module("class :\n    pass\n").is_err());
+        assert!(parse_module("def f(:\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_python2_statements() {
+        let error = parse_module("def f():\n    x = 1; print \"x\"\n").unwrap_err();
+        assert_eq!(
+            error.message,
+            "Missing parentheses in call to 'print'. Did you mean print(...)?"
+        );
+        assert_eq!(error.position.line, 2);
+        assert!(parse_module("exec code in namespace\n").is_err());
+        assert_eq!(
+            parse_module("try:\n    pass\nexcept ValueError, error:\n    pass\n")
+                .unwrap_err()
+                .message,
+            "multiple exception types must be parenthesized"
+        );
+
+        let python3 = "print\nprint >> out, x\nprint(x)\ny = print if x else exec\n";
+        assert!(parse_module(python3).is_ok());
+    }
+
+    #[test]
+    fn test_parse_reports_first_error() {
+        let error = parse_module("print 'x'\ny = $\n").unwrap_err();
+        assert!(error.message.starts_with("Missing parentheses"));
+        let error = parse_module("y = $\nprint 'x'\n").unwrap_err();
+        assert_eq!(error.message, "invalid syntax");
+    }
+
+    #[test]
+    fn test_parse_call() {
+        let call = parse_call("dataclasses.field(default=(1, 2), init=False)").unwrap();
+        assert_eq!(call.callee, "dataclasses.field");
+        assert_eq!(call.name(), "field");
+        assert_eq!(call.keyword("default").as_deref(), Some("(1, 2)"));
+        assert_eq!(call.keyword("init").as_deref(), Some("False"));
+        assert_eq!(parse_call("attr.ib(3)").unwrap().positional(), vec!["3"]);
+
+        assert_eq!(parse_call("field"), None);
+        assert_eq!(parse_call("field()()"), None);
+        assert_eq!(parse_call("field() or []"), None);
+        assert_eq!(parse_call("[field()]"), None);
+    }
+
+    #[test]
+    fn test_literal_value() {
+        assert_eq!(literal_value("'users'"), Some("users".to_string()));
+        assert_eq!(literal_value("r'\\d'"), Some("\\d".to_string()));
+        assert_eq!(literal_value("b'raw'"), None);
+        assert_eq!(literal_value("Integer"), None);
+        assert_eq!(literal_value("name + 'x'"), None);
+    }
+
+    #[test]
+    fn test_parse_dict() {
+        assert_eq!(
+            parse_dict("{'extra': 'forbid', \"frozen\": True,}"),
+            Some(vec![
+                ("extra".to_string(), "'forbid'".to_string()),
+                ("frozen".to_string(), "True".to_string()),
+            ])
+        );
+        assert_eq!(parse_dict("{}"), Some(vec![]));
+        assert_eq!(parse_dict("{key: 1}"), None);
+        assert_eq!(parse_dict("dict(a=1)"), None);
+    }
+
+    #[test]
+    fn test_parse_sequence() {
+        assert_eq!(
+            parse_sequence("['RED', ('GREEN', 2),]"),
+            Some(vec!["'RED'".to_string(), "('GREEN', 2)".to_string()])
+        );
+        assert_eq!(parse_sequence("()"), Some(vec![]));
+        assert_eq!(parse_sequence("(a)(b)"), None);
+        assert_eq!(parse_sequence("'RED GREEN'"), None);
+    }
+}
@@ -0,0 +1,297 @@
+/*
+JonesCLI
+
+Author: Vlad Nedelcu
+Date: Jul 2021
+License: MIT
+
+Copyright 2021 Vlad Nedelcu
+*/
+use std::path::Path;
+
+use serde::Deserialize;
+
+use super::{encoding, NOTEBOOK_EXTENSION};
+
+const CODE_CELL: &str = "code";
+const CELL_MAGIC: &str = "%%";
+const ESCAPES: [char; 2] = ['%', '!'];
+
+#[derive(Deserialize)]
+struct NotebookDocument {
+    #[serde(default)]
+    cells: Vec<CellData>,
+}
+
+#[derive(Deserialize)]
+struct CellData {
+    cell_type: String,
+    #[serde(default)]
+    source: CellSource,
+}
+
+/// Notebooks store cell sources either whole or split in lines.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CellSource {
+    Lines(Vec<String>),
+    Text(String),
+}
+impl Default for CellSource {
+    fn default() -> Self {
+        CellSource::Text(String::new())
+    }
+}
+
+/// A code cell, by its position in the notebook (counted from 1, markdown
+/// cells included) and the line of the joined source it starts on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CellSpan {
+    pub cell: usize,
+    pub first_line: usize,
+}
+
+/// The code cells of a notebook joined into a single module.
+#[derive(Debug, PartialEq)]
+pub struct NotebookSource {
+    pub source: String,
+    pub cells: Vec<CellSpan>,
+}
+impl NotebookSource {
+    /// The cell holding a line of the joined source, with the line number
+    /// within that cell.
+    pub fn cell_line(&self, line: usize) -> Option<(usize, usize)> {
+        self.cells
+            .iter()
+            .rev()
+            .find(|span| span.first_line <= line)
+            .map(|span| (span.cell, line - span.first_line + 1))
+    }
+}
+
+pub fn is_notebook(file_path: &Path) -> bool {
+    file_path
+        .extension()
+        .is_some_and(|extension| extension == NOTEBOOK_EXTENSION)
+}
+
+pub fn read_notebook(file_path: &Path) -> Result<NotebookSource, String> {
+    let content = encoding::read_source(file_path)?;
+    join_cells(&content).map_err(|error| error.to_string())
+}
+
+/// Joins the code cells of a notebook document. IPython magics and shell
+/// escapes are blanked out, so that lines keep their numbers, and cells
+/// running a cell magic such as `%%bash` are left out.
+pub fn join_cells(content: &str) -> Result<NotebookSource, serde_json::Error> {
+    let document: NotebookDocument = serde_json::from_str(content)?;
+    let mut lines: Vec<&str> = Vec::new();
+    let mut cells = Vec::new();
+    let sources = document
+        .cells
+        .iter()
+        .enumerate()
+        .filter(|(_, cell)| cell.cell_type == CODE_CELL)
+        .map(|(index, cell)| {
+            let source = match &cell.source {
+                CellSource::Lines(lines) => lines.concat(),
+                CellSource::Text(text) => text.clone(),
+            };
+            (index + 1, source)
+        })
+        .collect::<Vec<(usize, String)>>();
+
+    for (cell, source) in sources.iter() {
+        if source.trim_start().starts_with(CELL_MAGIC) {
+            continue;
+        }
+        cells.push(CellSpan {
+            cell: *cell,
+            first_line: lines.len() + 1,
+        });
+        lines.extend(blank_escapes(source));
+    }
+
+    Ok(NotebookSource {
+        source: lines.join("\n") + "\n",
+        cells,
+    })
+}
+
+/// The lines of a cell, with its IPython escapes blanked. Only a line which
+/// starts a logical line can be one, not a line continuing brackets, a
+/// string or a backslash.
+fn blank_escapes(source: &str) -> Vec<&str> {
+    let mut continuation = Continuation::default();
+    source
+        .split('\n')
+        .map(|line| {
+            if !continuation.is_open() && is_escape(line) {
+                return "";
+            }
+            continuation.scan(line);
+            line
+        })
+        .collect()
+}
+
+/// What a line leaves open for the next one: brackets, a string, or a
+/// backslash at its end.
+#[derive(Default)]
+struct Continuation {
+    depth: usize,
+    /// The quote of the open string, and whether it is triple.
+    string: Option<(char, bool)>,
+    backslash: bool,
+}
+impl Continuation {
+    fn is_open(&self) -> bool {
+        self.depth > 0 || self.string.is_some() || self.backslash
+    }
+
+    fn scan(&mut self, line: &str) {
+        let characters = line.trim_end_matches('\r').chars().collect::<Vec<char>>();
+        self.backslash = false;
+        let mut index = 0;
+        while index < characters.len() {
+            let character = characters[index];
+            let last = index + 1 == characters.len();
+            match self.string {
+                Some(_) if character == '\\' => {
+                    self.backslash = last;
+                    index += 1;
+                }
+                Some((quote, false)) if character == quote => self.string = None,
+                Some((quote, true)) if characters[index..].starts_with(&[quote; 3]) => {
+                    self.string = None;
+                    index += 2;
+                }
+                Some(_) => {}
+                None => match character {
+                    '#' => break,
+                    '\'' | '"' => {
+                        let triple = characters[index..].starts_with(&[character; 3]);
+                        self.string = Some((character, triple));
+                        if triple {
+                            index += 2;
+                        }
+                    }
+                    '(' | '[' | '{' => self.depth += 1,
+                    ')' | ']' | '}' => self.depth = self.depth.saturating_sub(1),
+                    '\\' => self.backslash = last,
+                    _ => {}
+                },
+            }
+            index += 1;
+        }
+        // a single quoted string ends with its line, unless continued
+        if matches!(self.string, Some((_, false))) && !self.backslash {
+            self.string = None;
+        }
+    }
+}
+
+/// Whether a line is an IPython magic or shell escape, `%timeit f()`,
+/// `!pip install x` or `files = !ls`, which Python cannot parse.
+fn is_escape(line: &str) -> bool {
+    let line = line.trim_start();
+    if line.starts_with(ESCAPES) {
+        return true;
+    }
+    match line.split_once('=') {
+        Some((targets, value)) => {
+            let is_target = targets.chars().all(|character| {
+                character.is_alphanumeric()
+                    || character == '_'
+                    || character == ','
+                    || character == ' '
+            });
+            is_target && !targets.trim().is_empty() && value.trim_start().starts_with(ESCAPES)
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{blank_escapes, is_escape, join_cells, CellSpan};
+    use crate::joneslib::native::load_python_object;
+
+    static NOTEBOOK: &str = r##"{
+        "cells": [
+            {"cell_type": "markdown", "source": ["# Analysis\n"]},
+            {"cell_type": "code", "source": ["%matplotlib inline\n", "import pandas as pd"]},
+            {"cell_type": "code", "source": "%%bash\nls -la"},
+            {"cell_type": "code", "source": ["files = !ls\n", "class Model:\n", "    x: int = 0\n"]}
+        ],
+        "nbformat": 4
+    }"##;
+
+    #[test]
+    fn test_join_cells() {
+        let notebook = join_cells(NOTEBOOK).unwrap();
+        assert_eq!(
+            notebook.source,
+            "\nimport pandas as pd\n\nclass Model:\n    x: int = 0\n\n"
+        );
+        assert_eq!(
+            notebook.cells,
+            vec![
+                CellSpan {
+                    cell: 2,
+                    first_line: 1
+                },
+                CellSpan {
+                    cell: 4,
+                    first_line: 3
+                },
+            ]
+        );
+        assert_eq!(notebook.cell_line(4), Some((4, 2)));
+        assert_eq!(notebook.cell_line(2), Some((2, 2)));
+    }
+
+    #[test]
+    fn test_load_notebook_class() {
+        let test_file = std::env::temp_dir().join("jones_test_load_notebook_class.ipynb");
+        std::fs::write(&test_file, NOTEBOOK).unwrap();
+
+        let class = load_python_object(&test_file, "analysis", "Model").unwrap();
+        let location = class.location.unwrap();
+        assert_eq!((location.cell, location.line), (Some(4), 2));
+        assert_eq!(class.attributes[0].location.as_ref().unwrap().line, 3);
+
+        std::fs::remove_file(&test_file).unwrap();
+    }
+
+    #[test]
+    fn test_is_escape() {
+        assert!(is_escape("!pip install jones"));
+        assert!(is_escape("    %time run()"));
+        assert!(is_escape("out, err = !make"));
+        assert!(!is_escape("x != y"));
+        assert!(!is_escape("x = y % 2"));
+        assert!(!is_escape("config['a'] = 1"));
+    }
+
+    #[test]
+    fn test_blank_escapes_of_logical_lines_only() {
+        let source = "%time run()\nmessage = ('%s items'\n    % count)\nsame = (a\n    != b)\ndoc = \"\"\"\n!not a shell escape\n\"\"\"\nx = 1 + \\\n    !y\n!ls";
+        assert_eq!(
+            blank_escapes(source),
+            vec![
+                "",
+                "message = ('%s items'",
+                "    % count)",
+                "same = (a",
+                "    != b)",
+                "doc = \"\"\"",
+                "!not a shell escape",
+                "\"\"\"",
+                "x = 1 + \\",
+                "    !y",
+                "",
+            ]
+        );
+    }
+}
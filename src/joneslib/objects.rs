@@ -8,28 +8,220 @@ License: MIT
 Copyright 2021 Vlad Nedelcu
 */
 
-use ansi_term::Colour;
+use ansi_term::{Colour, Style};
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
-const SELF_PARAMETER: [&str; 2] = ["self", "cls"];
+const SELF_TYPE: &str = "Self";
+const MISSING_TYPE: &str = "None";
+const STATIC_DECORATORS: [&str; 1] = ["staticmethod"];
+const CLASS_DECORATORS: [&str; 1] = ["classmethod"];
+const PROPERTY_DECORATORS: [&str; 3] = ["property", "cached_property", "abstractproperty"];
+const ABSTRACT_DECORATORS: [&str; 4] = [
+    "abstractmethod",
+    "abstractproperty",
+    "abstractclassmethod",
+    "abstractstaticmethod",
+];
 
-#[derive(Debug, PartialEq)]
+/// Where a definition sits in the sources, with 1-based lines and 0-based
+/// columns like the Python `ast` module. In notebooks lines are counted from
+/// the start of their cell.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Location {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+    /// The notebook cell, counted from 1.
+    #[serde(default)]
+    pub cell: Option<usize>,
+}
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.cell {
+            Some(cell) => write!(f, "{}, cell {}, line {}", self.file, cell, self.line),
+            None => write!(f, "{}:{}", self.file, self.line),
+        }
+    }
+}
+
+/// What a top-level name of a module is defined as.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DefinitionKind {
+    Class,
+    Function,
+    Constant,
+}
+impl fmt::Display for DefinitionKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            DefinitionKind::Class => "class",
+            DefinitionKind::Function => "function",
+            DefinitionKind::Constant => "constant",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A name found in the project: a class, with its qualified name, or a
+/// module-level function or constant.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Definition {
+    pub name: String,
+    pub file: String,
+    #[serde(default = "class_kind")]
+    pub kind: DefinitionKind,
+}
+impl Definition {
+    pub fn new(name: String, file: String, kind: DefinitionKind) -> Self {
+        Definition { name, file, kind }
+    }
+}
+
+fn class_kind() -> DefinitionKind {
+    DefinitionKind::Class
+}
+
+/// A file a project scan had to leave out, with the reason.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct SkippedFile {
+    pub file: String,
+    pub error: String,
+    #[serde(default)]
+    pub line: Option<usize>,
+    /// The notebook cell the line is counted in.
+    #[serde(default)]
+    pub cell: Option<usize>,
+}
+impl SkippedFile {
+    /// Where the error is, as locations get shown.
+    pub fn place(&self) -> String {
+        match self.line {
+            Some(line) => Location {
+                file: self.file.clone(),
+                line,
+                cell: self.cell,
+                ..Default::default()
+            }
+            .to_string(),
+            None => self.file.clone(),
+        }
+    }
+}
+impl fmt::Display for SkippedFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            ":: {} -> {}",
+            Colour::Purple.paint(self.place()),
+            Colour::Red.paint(&self.error)
+        )
+    }
+}
+
+/// The definitions found in a project, and the files which could not be read.
+#[derive(Debug, Default, PartialEq)]
+pub struct ProjectScan {
+    pub definitions: Vec<Definition>,
+    pub skipped: Vec<SkippedFile>,
+}
+
+/// Where a type annotation was read from: the module itself, or the `.pyi`
+/// stub merged into it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+pub enum TypeSource {
+    #[default]
+    Implementation,
+    Stub,
+}
+
+/// The dimmed `<stub>` marker of a type taken from a stub, if it was.
+pub fn type_source_marker(source: TypeSource) -> String {
+    match source {
+        TypeSource::Implementation => String::new(),
+        TypeSource::Stub => format!(" {}", Style::new().dimmed().paint("<stub>")),
+    }
+}
+
+/// How an argument can be passed, mirroring `inspect.Parameter.kind`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParameterKind {
+    PositionalOnly,
+    Regular,
+    KeywordOnly,
+    VarPositional,
+    VarKeyword,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct Parameter {
     pub name: String,
     pub static_type: String,
+    pub kind: ParameterKind,
+    pub default: Option<String>,
+    pub location: Option<Location>,
+    pub type_source: TypeSource,
 }
 impl Parameter {
     pub fn new(name: String, static_type: String) -> Self {
-        let annotation = if SELF_PARAMETER.contains(&&*name) {
-            "Self".to_string()
-        } else {
-            static_type.clone()
-        };
-
         Parameter {
             name,
-            static_type: annotation,
+            static_type,
+            kind: ParameterKind::Regular,
+            default: None,
+            location: None,
+            type_source: TypeSource::Implementation,
+        }
+    }
+
+    pub fn with_kind(mut self, kind: ParameterKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    pub fn with_default(mut self, default: Option<String>) -> Self {
+        self.default = default;
+        self
+    }
+
+    pub fn with_location(mut self, location: Option<Location>) -> Self {
+        self.location = location;
+        self
+    }
+
+    /// The parameter name with its `*` or `**` prefix.
+    pub fn display_name(&self) -> String {
+        match self.kind {
+            ParameterKind::VarPositional => format!("*{}", self.name),
+            ParameterKind::VarKeyword => format!("**{}", self.name),
+            _ => self.name.clone(),
+        }
+    }
+
+    /// Whether the parameter has a type annotation of its own.
+    pub fn is_annotated(&self) -> bool {
+        self.static_type != MISSING_TYPE && self.static_type != SELF_TYPE
+    }
+
+    /// The parameter as written in a Python signature.
+    pub fn signature(&self) -> String {
+        let mut signature = self.display_name();
+        let annotated = self.is_annotated();
+        if annotated {
+            signature.push_str(&format!(": {}", self.static_type));
+        }
+        if let Some(default) = &self.default {
+            if annotated {
+                signature.push_str(&format!(" = {}", default));
+            } else {
+                signature.push_str(&format!("={}", default));
+            }
         }
+        signature
     }
 }
 impl fmt::Display for Parameter {
@@ -37,45 +229,485 @@ impl fmt::Display for Parameter {
         write!(
             f,
             "  * {}: {}",
-            Colour::Purple.paint(&self.name),
+            Colour::Purple.paint(self.display_name()),
             Colour::Green.paint(&self.static_type)
+        )?;
+        if let Some(default) = &self.default {
+            write!(f, " = {}", Colour::Blue.paint(default))?;
+        }
+        write!(f, "{}", type_source_marker(self.type_source))
+    }
+}
+
+/// The dimmed `path:line` of a definition, appended to its output line.
+pub fn location_suffix(location: &Option<Location>) -> String {
+    match location {
+        Some(location) => format!(" {}", Style::new().dimmed().paint(location.to_string())),
+        None => String::new(),
+    }
+}
+
+/// How a method is bound, derived from its decorators.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MethodKind {
+    Instance,
+    Class,
+    Static,
+    PropertyGetter,
+    PropertySetter,
+    PropertyDeleter,
+}
+impl MethodKind {
+    pub fn is_property(&self) -> bool {
+        matches!(
+            self,
+            MethodKind::PropertyGetter | MethodKind::PropertySetter | MethodKind::PropertyDeleter
         )
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
 pub struct Method {
     pub name: String,
     pub parameters: Vec<Parameter>,
     pub output: String,
+    pub decorators: Vec<String>,
+    pub is_async: bool,
+    pub is_generator: bool,
+    pub docstring: Option<String>,
+    /// Generated by a library rather than written in the class, like the
+    /// `__init__` of a dataclass.
+    pub is_synthesized: bool,
+    pub location: Option<Location>,
+    /// Where the return annotation comes from.
+    pub output_source: TypeSource,
+}
+impl Method {
+    /// Name of each decorator without its module path and call arguments,
+    /// `abc.abstractmethod` and `functools.lru_cache(1)` become
+    /// `abstractmethod` and `lru_cache`.
+    fn decorator_names(&self) -> Vec<&str> {
+        self.decorators
+            .iter()
+            .map(|decorator| {
+                let callee = decorator.split('(').next().unwrap_or(decorator);
+                callee.rsplit('.').next().unwrap_or(callee).trim()
+            })
+            .collect()
+    }
+
+    pub fn kind(&self) -> MethodKind {
+        let mut kind = MethodKind::Instance;
+        for (decorator, name) in self.decorators.iter().zip(self.decorator_names()) {
+            let is_accessor = decorator.contains('.') && !decorator.contains('(');
+            if STATIC_DECORATORS.contains(&name) || name == "abstractstaticmethod" {
+                kind = MethodKind::Static;
+            } else if CLASS_DECORATORS.contains(&name) || name == "abstractclassmethod" {
+                kind = MethodKind::Class;
+            } else if PROPERTY_DECORATORS.contains(&name) || (is_accessor && name == "getter") {
+                kind = MethodKind::PropertyGetter;
+            } else if is_accessor && name == "setter" {
+                kind = MethodKind::PropertySetter;
+            } else if is_accessor && name == "deleter" {
+                kind = MethodKind::PropertyDeleter;
+            }
+        }
+        kind
+    }
+
+    pub fn is_abstract(&self) -> bool {
+        self.decorator_names()
+            .iter()
+            .any(|name| ABSTRACT_DECORATORS.contains(name))
+    }
+
+    /// Marks the implicit `self`/`cls` parameter of bound methods with the
+    /// `Self` type, when it has no annotation of its own.
+    pub fn annotate_receiver(mut self) -> Self {
+        if self.kind() == MethodKind::Static {
+            return self;
+        }
+        if let Some(receiver) = self.parameters.first_mut() {
+            let positional = matches!(
+                receiver.kind,
+                ParameterKind::PositionalOnly | ParameterKind::Regular
+            );
+            if positional && receiver.static_type == MISSING_TYPE {
+                receiver.static_type = SELF_TYPE.to_string();
+            }
+        }
+        self
+    }
+
+    /// First line of the docstring, the summary by Python conventions.
+    pub fn docstring_summary(&self) -> Option<&str> {
+        self.docstring
+            .as_deref()
+            .and_then(|docstring| docstring.lines().next())
+            .filter(|summary| !summary.is_empty())
+    }
+
+    /// Short labels describing the method, shown next to its signature.
+    pub fn labels(&self) -> Vec<&'static str> {
+        let mut labels = Vec::new();
+        match self.kind() {
+            MethodKind::Class => labels.push("classmethod"),
+            MethodKind::Static => labels.push("staticmethod"),
+            MethodKind::PropertyGetter => labels.push("getter"),
+            MethodKind::PropertySetter => labels.push("setter"),
+            MethodKind::PropertyDeleter => labels.push("deleter"),
+            MethodKind::Instance => {}
+        }
+        if self.is_abstract() {
+            labels.push("abstract");
+        }
+        if self.is_async {
+            labels.push("async");
+        }
+        if self.is_generator {
+            labels.push("generator");
+        }
+        if self.is_synthesized {
+            labels.push("synthesized");
+        }
+        labels
+    }
+
+    /// The parameter list as Python writes it, with the `/` and `*` markers
+    /// separating positional-only and keyword-only parameters.
+    pub fn signature(&self) -> String {
+        let mut parts = Vec::new();
+        let mut previous = None;
+        for parameter in self.parameters.iter() {
+            if previous == Some(&ParameterKind::PositionalOnly)
+                && parameter.kind != ParameterKind::PositionalOnly
+            {
+                parts.push("/".to_string());
+            }
+            if parameter.kind == ParameterKind::KeywordOnly
+                && !matches!(
+                    previous,
+                    Some(ParameterKind::KeywordOnly) | Some(ParameterKind::VarPositional)
+                )
+            {
+                parts.push("*".to_string());
+            }
+            parts.push(parameter.signature());
+            previous = Some(&parameter.kind);
+        }
+        if previous == Some(&ParameterKind::PositionalOnly) {
+            parts.push("/".to_string());
+        }
+        format!("({})", parts.join(", "))
+    }
 }
 impl fmt::Display for Method {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            ":: [{}] -> {}",
+            ":: [{}] {} -> {}",
             Colour::Yellow.paint(&self.name),
+            self.signature(),
             Colour::Cyan.paint(&self.output)
+        )?;
+        write!(f, "{}", type_source_marker(self.output_source))?;
+        let labels = self.labels();
+        if !labels.is_empty() {
+            write!(
+                f,
+                " {}",
+                Colour::Blue.paint(format!("<{}>", labels.join(", ")))
+            )?;
+        }
+        write!(f, "{}", location_suffix(&self.location))
+    }
+}
+
+/// Whether an attribute is set on the class body or on instances.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttributeScope {
+    Class,
+    Instance,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Attribute {
+    pub name: String,
+    pub annotation: Option<String>,
+    pub default: Option<String>,
+    pub scope: AttributeScope,
+    /// The method assigning an instance attribute, e.g. `__init__`.
+    pub method: Option<String>,
+    pub location: Option<Location>,
+    pub type_source: TypeSource,
+}
+impl fmt::Display for Attribute {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, ":: [{}]", Colour::Yellow.paint(&self.name))?;
+        if let Some(annotation) = &self.annotation {
+            write!(f, ": {}", Colour::Green.paint(annotation))?;
+            write!(f, "{}", type_source_marker(self.type_source))?;
+        }
+        if let Some(default) = &self.default {
+            write!(f, " = {}", Colour::Blue.paint(default))?;
+        }
+        let origin = match (&self.scope, &self.method) {
+            (AttributeScope::Instance, Some(method)) => format!("<instance, {}>", method),
+            (AttributeScope::Instance, None) => "<instance>".to_string(),
+            (AttributeScope::Class, _) => "<class>".to_string(),
+        };
+        write!(f, " {}", Colour::Purple.paint(origin))?;
+        write!(f, "{}", location_suffix(&self.location))
+    }
+}
+
+/// The library generating the members of a class from its field declarations.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub enum ModelKind {
+    Dataclass,
+    Attrs,
+    NamedTuple,
+    TypedDict,
+    Pydantic,
+    Django,
+    SqlAlchemy,
+    Enum,
+}
+impl fmt::Display for ModelKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ModelKind::Dataclass => "dataclass",
+            ModelKind::Attrs => "attrs",
+            ModelKind::NamedTuple => "NamedTuple",
+            ModelKind::TypedDict => "TypedDict",
+            ModelKind::Pydantic => "pydantic",
+            ModelKind::Django => "django",
+            ModelKind::SqlAlchemy => "sqlalchemy",
+            ModelKind::Enum => "enum",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The class a relation field points to, with the file defining it once it
+/// has been found in the project.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Relation {
+    pub target: String,
+    pub file: Option<String>,
+}
+impl fmt::Display for Relation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "-> {}", Colour::Cyan.paint(&self.target))?;
+        if let Some(file) = &self.file {
+            write!(f, " {}", Style::new().dimmed().paint(format!("({})", file)))?;
+        }
+        Ok(())
+    }
+}
+
+/// A field declared on a model class, such as `tags: list = field(default_factory=list)`
+/// on a dataclass.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Field {
+    pub name: String,
+    pub annotation: Option<String>,
+    pub default: Option<String>,
+    /// The constructor parameter setting the field, `None` when the field is
+    /// left out of the constructor.
+    pub parameter: Option<Parameter>,
+    /// Declaration details worth showing, e.g. `kw_only` or `default_factory=list`.
+    pub options: Vec<String>,
+    pub relation: Option<Relation>,
+    pub primary_key: bool,
+    pub location: Option<Location>,
+    pub type_source: TypeSource,
+}
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, ":: [{}]", Colour::Yellow.paint(&self.name))?;
+        if let Some(annotation) = &self.annotation {
+            write!(f, ": {}", Colour::Green.paint(annotation))?;
+            write!(f, "{}", type_source_marker(self.type_source))?;
+        }
+        if let Some(default) = &self.default {
+            write!(f, " = {}", Colour::Blue.paint(default))?;
+        }
+        if let Some(relation) = &self.relation {
+            write!(f, " {}", relation)?;
+        }
+        if !self.options.is_empty() {
+            write!(
+                f,
+                " {}",
+                Colour::Purple.paint(format!("<{}>", self.options.join(", ")))
+            )?;
+        }
+        write!(f, "{}", location_suffix(&self.location))
+    }
+}
+
+/// A member of an enum, with its value as written, or as computed for
+/// `auto()` when it can be.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Member {
+    pub name: String,
+    pub value: String,
+    /// How the value was obtained, e.g. `auto()` or `alias of RED`.
+    pub options: Vec<String>,
+    pub location: Option<Location>,
+}
+impl fmt::Display for Member {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            ":: [{}] = {}",
+            Colour::Yellow.paint(&self.name),
+            Colour::Blue.paint(&self.value)
+        )?;
+        if !self.options.is_empty() {
+            write!(
+                f,
+                " {}",
+                Colour::Purple.paint(format!("<{}>", self.options.join(", ")))
+            )?;
+        }
+        write!(f, "{}", location_suffix(&self.location))
+    }
+}
+
+/// A validator method of a model and the fields it checks. Validators of the
+/// whole model check no field in particular.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Validator {
+    pub method: String,
+    pub fields: Vec<String>,
+    /// The validator decorator and its settings, e.g. `field_validator, mode='before'`.
+    pub options: Vec<String>,
+    pub location: Option<Location>,
+}
+impl fmt::Display for Validator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let fields = if self.fields.is_empty() {
+            "<model>".to_string()
+        } else {
+            self.fields.join(", ")
+        };
+        write!(
+            f,
+            ":: [{}] -> {} {}{}",
+            Colour::Yellow.paint(&self.method),
+            Colour::Green.paint(fields),
+            Colour::Blue.paint(format!("<{}>", self.options.join(", "))),
+            location_suffix(&self.location)
         )
     }
 }
 
-#[derive(Debug, PartialEq)]
+/// A keyword of the class definition, such as `metaclass=ABCMeta`. A keyword
+/// without name is an unpacked mapping, `**options`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Keyword {
+    pub name: Option<String>,
+    pub value: String,
+}
+impl fmt::Display for Keyword {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{}={}", name, self.value),
+            None => write!(f, "**{}", self.value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
 pub struct PythonClass {
     pub name: String,
     pub methods: Vec<Method>,
     pub inheritance: Vec<String>,
     pub docstring: String,
+    pub attributes: Vec<Attribute>,
+    pub keywords: Vec<Keyword>,
+    /// The `__qualname__` of the class, e.g. `Outer.Inner`.
+    pub qualified_name: String,
+    pub nested_classes: Vec<PythonClass>,
+    pub decorators: Vec<String>,
+    pub model: Option<ModelKind>,
+    pub fields: Vec<Field>,
+    /// Settings of the model, like the pydantic `model_config`.
+    pub config: Vec<Keyword>,
+    pub validators: Vec<Validator>,
+    /// Attributes holding a manager, such as the `objects` of a Django model.
+    pub managers: Vec<Attribute>,
+    /// The database table a model maps to.
+    pub table: Option<String>,
+    pub members: Vec<Member>,
+    pub location: Option<Location>,
+    /// The `.pyi` stub merged into the class, if any.
+    pub stub: Option<String>,
 }
 impl fmt::Display for PythonClass {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let inheritance_display = self.inheritance.join(", ");
         write!(
             f,
-            "# Name [{}]\n--------\n* docstring: {}\n* inherits -> {}\n\n# Methods\n-------",
-            Colour::Cyan.paint(&self.name),
+            "# Name [{}]\n--------\n* docstring: {}\n* inherits -> {}",
+            Colour::Cyan.paint(&self.qualified_name),
             Colour::Yellow.paint(&self.docstring),
             Colour::Green.paint(inheritance_display)
-        )
+        )?;
+        if !self.keywords.is_empty() {
+            let keywords_display = self
+                .keywords
+                .iter()
+                .map(|keyword| keyword.to_string())
+                .collect::<Vec<String>>()
+                .join(", ");
+            write!(
+                f,
+                "\n* keywords -> {}",
+                Colour::Purple.paint(keywords_display)
+            )?;
+        }
+        if let Some(model) = &self.model {
+            write!(
+                f,
+                "\n* model -> {}",
+                Colour::Purple.paint(model.to_string())
+            )?;
+        }
+        if !self.config.is_empty() {
+            let config_display = self
+                .config
+                .iter()
+                .map(|setting| setting.to_string())
+                .collect::<Vec<String>>()
+                .join(", ");
+            write!(f, "\n* config -> {}", Colour::Purple.paint(config_display))?;
+        }
+        if let Some(table) = &self.table {
+            write!(f, "\n* table -> {}", Colour::Purple.paint(table))?;
+        }
+        let primary_key = self
+            .fields
+            .iter()
+            .filter(|field| field.primary_key)
+            .map(|field| field.name.as_str())
+            .collect::<Vec<&str>>();
+        if !primary_key.is_empty() {
+            write!(
+                f,
+                "\n* primary key -> {}",
+                Colour::Purple.paint(primary_key.join(", "))
+            )?;
+        }
+        if let Some(location) = &self.location {
+            write!(f, "\n* location -> {}", location)?;
+        }
+        if let Some(stub) = &self.stub {
+            write!(f, "\n* stub -> {}", stub)?;
+        }
+        Ok(())
     }
 }
@@ -7,72 +7,268 @@ License: MIT
 
 Copyright 2021 Vlad Nedelcu
 */
-use super::objects::{Method, Parameter, PythonClass};
-
-static PARAMETER_SEPARATOR: &str = "||";
-
-pub fn parse_method_parameter(parameters: Vec<String>) -> Vec<Parameter> {
-    let mut parsed_parameters = Vec::new();
-    for parameter in parameters.iter() {
-        let values = parameter
-            .split(PARAMETER_SEPARATOR)
-            .filter(|x| !x.is_empty())
-            .collect::<Vec<&str>>();
-        if values.len() != 2 {
-            continue;
+use std::fmt;
+
+use serde::Deserialize;
+
+use super::frameworks::enums;
+use super::objects::{
+    Attribute, AttributeScope, Definition, Keyword, Location, Method, Parameter, ParameterKind,
+    ProjectScan, PythonClass, SkippedFile, TypeSource,
+};
+
+/// Version of the JSON documents printed by the loader scripts. Bump it on
+/// both sides, here and in `scripts/common.py`, whenever the shape of the
+/// documents changes.
+pub const SCHEMA_VERSION: u32 = 13;
+
+static MISSING_VALUE: &str = "None";
+
+#[derive(Debug, PartialEq)]
+pub enum ProtocolError {
+    InvalidDocument(String),
+    UnsupportedVersion(u32),
+}
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProtocolError::InvalidDocument(error) => {
+                write!(f, "Invalid loader script output: {}", error)
+            }
+            ProtocolError::UnsupportedVersion(version) => write!(
+                f,
+                "Loader script schema version {} is not supported (expected {})",
+                version, SCHEMA_VERSION
+            ),
         }
-        let (param_name, param_annotation) = (values[0], values[1]);
-        parsed_parameters.push(Parameter::new(
-            param_name.to_string(),
-            param_annotation.to_string(),
-        ));
     }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ProjectDocument {
+    pub version: u32,
+    pub definitions: Vec<Definition>,
+    #[serde(default)]
+    pub skipped: Vec<SkippedFile>,
+}
+
+/// Output of the object script, holding the requested class or function
+/// when the script found it in the file, or why the file was skipped.
+#[derive(Debug, Deserialize)]
+pub struct ObjectDocument {
+    pub version: u32,
+    #[serde(default)]
+    pub class: Option<ClassData>,
+    #[serde(default)]
+    pub function: Option<MethodData>,
+    /// The top-level call assigned to the requested name, when no class
+    /// matches it, which may create an enum through the functional API.
+    #[serde(default)]
+    pub functional_enum: Option<FunctionalEnumData>,
+    #[serde(default)]
+    pub skipped: Option<SkippedFile>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FunctionalEnumData {
+    pub name: String,
+    pub expression: String,
+    #[serde(default)]
+    pub location: Option<Location>,
+}
 
-    parsed_parameters
+#[derive(Debug, Deserialize)]
+pub struct ClassData {
+    pub name: String,
+    pub docstring: Option<String>,
+    pub inheritance: Vec<String>,
+    pub methods: Vec<MethodData>,
+    #[serde(default)]
+    pub attributes: Vec<AttributeData>,
+    #[serde(default)]
+    pub keywords: Vec<Keyword>,
+    pub qualified_name: String,
+    #[serde(default)]
+    pub nested_classes: Vec<ClassData>,
+    #[serde(default)]
+    pub decorators: Vec<String>,
+    #[serde(default)]
+    pub location: Option<Location>,
 }
 
-pub fn parse_method(methods_data: Vec<(String, String, String)>) -> Vec<Method> {
-    let mut parsed_methods = Vec::new();
-    for method in methods_data.iter() {
-        let (method_name, method_parameters, method_output) =
-            (method.0.clone(), method.1.clone(), method.2.clone());
-        let raw_parameters = method_parameters
-            .split(",")
-            .map(|x| x.to_string())
-            .filter(|x| !x.is_empty())
-            .collect::<Vec<String>>();
-        let parameters = parse_method_parameter(raw_parameters);
-        parsed_methods.push(Method {
-            name: method_name.to_string(),
-            parameters,
-            output: method_output,
-        });
+#[derive(Debug, Deserialize)]
+pub struct MethodData {
+    pub name: String,
+    pub parameters: Vec<ParameterData>,
+    pub output: Option<String>,
+    #[serde(default)]
+    pub decorators: Vec<String>,
+    #[serde(default)]
+    pub is_async: bool,
+    #[serde(default)]
+    pub is_generator: bool,
+    #[serde(default)]
+    pub docstring: Option<String>,
+    #[serde(default)]
+    pub location: Option<Location>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ParameterData {
+    pub name: String,
+    pub annotation: Option<String>,
+    #[serde(default = "regular_kind")]
+    pub kind: ParameterKind,
+    #[serde(default)]
+    pub default: Option<String>,
+    #[serde(default)]
+    pub location: Option<Location>,
+}
+
+fn regular_kind() -> ParameterKind {
+    ParameterKind::Regular
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AttributeData {
+    pub name: String,
+    pub annotation: Option<String>,
+    pub default: Option<String>,
+    pub scope: AttributeScope,
+    pub method: Option<String>,
+    #[serde(default)]
+    pub location: Option<Location>,
+}
+
+fn check_version(version: u32) -> Result<(), ProtocolError> {
+    if version != SCHEMA_VERSION {
+        return Err(ProtocolError::UnsupportedVersion(version));
     }
+    Ok(())
+}
 
-    parsed_methods
+/// Parses the output of the project script into the definitions it found,
+/// and the files it could not parse.
+pub fn parse_project(output: &[u8]) -> Result<ProjectScan, ProtocolError> {
+    let document: ProjectDocument = serde_json::from_slice(output)
+        .map_err(|error| ProtocolError::InvalidDocument(error.to_string()))?;
+    check_version(document.version)?;
+
+    Ok(ProjectScan {
+        definitions: document.definitions,
+        skipped: document.skipped,
+    })
+}
+
+/// Parses the output of the object script. The document holds no class or
+/// function when the script did not find them in the file.
+pub fn parse_object_document(output: &[u8]) -> Result<ObjectDocument, ProtocolError> {
+    let document: ObjectDocument = serde_json::from_slice(output)
+        .map_err(|error| ProtocolError::InvalidDocument(error.to_string()))?;
+    check_version(document.version)?;
+    Ok(document)
 }
 
-pub fn parse_class(
-    name: String,
-    methods: Vec<(String, String, String)>,
-    docstring: String,
-    inheritance: Vec<String>,
-) -> PythonClass {
-    let methods = parse_method(methods);
+/// The class of an object document, possibly made by the functional API.
+pub fn document_class(document: ObjectDocument) -> Option<PythonClass> {
+    match (document.class, document.functional_enum) {
+        (Some(class), _) => Some(parse_class(class)),
+        (None, Some(functional)) => enums::functional_class(
+            &functional.name,
+            &functional.expression,
+            functional.location,
+        ),
+        (None, None) => None,
+    }
+}
+
+/// The function of an object document.
+pub fn document_function(document: ObjectDocument) -> Option<Method> {
+    document.function.map(parse_function)
+}
+
+pub fn parse_method_parameter(parameters: Vec<ParameterData>) -> Vec<Parameter> {
+    parameters
+        .into_iter()
+        .map(|parameter| {
+            Parameter::new(
+                parameter.name,
+                parameter
+                    .annotation
+                    .unwrap_or_else(|| MISSING_VALUE.to_string()),
+            )
+            .with_kind(parameter.kind)
+            .with_default(parameter.default)
+            .with_location(parameter.location)
+        })
+        .collect()
+}
+
+/// Builds a function from its data. Methods additionally get their receiver
+/// annotated, see `parse_method`.
+pub fn parse_function(method: MethodData) -> Method {
+    Method {
+        name: method.name,
+        parameters: parse_method_parameter(method.parameters),
+        output: method.output.unwrap_or_else(|| MISSING_VALUE.to_string()),
+        decorators: method.decorators,
+        is_async: method.is_async,
+        is_generator: method.is_generator,
+        docstring: method.docstring,
+        location: method.location,
+        ..Default::default()
+    }
+}
+
+pub fn parse_method(methods_data: Vec<MethodData>) -> Vec<Method> {
+    methods_data
+        .into_iter()
+        .map(|method| parse_function(method).annotate_receiver())
+        .collect()
+}
+
+pub fn parse_class(class: ClassData) -> PythonClass {
     PythonClass {
-        name,
-        methods,
-        docstring,
-        inheritance,
+        name: class.name,
+        methods: parse_method(class.methods),
+        docstring: class.docstring.unwrap_or_else(|| MISSING_VALUE.to_string()),
+        inheritance: class.inheritance,
+        attributes: parse_attributes(class.attributes),
+        keywords: class.keywords,
+        qualified_name: class.qualified_name,
+        nested_classes: class.nested_classes.into_iter().map(parse_class).collect(),
+        decorators: class.decorators,
+        location: class.location,
+        ..Default::default()
     }
 }
 
+pub fn parse_attributes(attributes: Vec<AttributeData>) -> Vec<Attribute> {
+    attributes
+        .into_iter()
+        .map(|attribute| Attribute {
+            name: attribute.name,
+            annotation: attribute.annotation,
+            default: attribute.default,
+            scope: attribute.scope,
+            method: attribute.method,
+            location: attribute.location,
+            type_source: TypeSource::Implementation,
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
+    use super::{AttributeScope, ParameterKind, ProtocolError, TypeSource};
+    use crate::joneslib::objects::DefinitionKind;
 
     #[test]
     fn test_parse_method_params_ok() {
-        let raw_parameters = vec!["self||str".to_string(), "cls||int".to_string()];
+        let raw_parameters = serde_json::from_str(
+            r#"[{"name": "self", "annotation": "str"}, {"name": "cls", "annotation": "int"}]"#,
+        )
+        .unwrap();
         let parsed_parameters = super::parse_method_parameter(raw_parameters);
         let expected_parameters = vec![
             super::Parameter::new("self".to_string(), "str".to_string()),
@@ -82,45 +278,69 @@ mod tests {
     }
 
     #[test]
-    fn test_parse_method_params_invalid_separator() {
-        let raw_parameters = vec!["self||str".to_string(), "cls|int".to_string()];
+    fn test_parse_method_params_no_type() {
+        let raw_parameters = serde_json::from_str(
+            r#"[{"name": "self", "annotation": "str"}, {"name": "xvalue", "annotation": null}]"#,
+        )
+        .unwrap();
         let parsed_parameters = super::parse_method_parameter(raw_parameters);
-        let expected_parameters =
-            vec![super::Parameter::new("self".to_string(), "str".to_string())];
+        let expected_parameters = vec![
+            super::Parameter::new("self".to_string(), "str".to_string()),
+            super::Parameter::new("xvalue".to_string(), "None".to_string()),
+        ];
         assert_eq!(parsed_parameters, expected_parameters);
     }
 
     #[test]
-    fn test_parse_method_params_no_type() {
-        let raw_parameters = vec!["self||str".to_string(), "xvalue||".to_string()];
+    fn test_parse_method_params_kinds() {
+        let raw_parameters = serde_json::from_str(
+            r#"[
+                {"name": "a", "annotation": null, "kind": "positional_only", "default": null},
+                {"name": "args", "annotation": null, "kind": "var_positional", "default": null},
+                {"name": "key", "annotation": "int", "kind": "keyword_only", "default": "1"}
+            ]"#,
+        )
+        .unwrap();
         let parsed_parameters = super::parse_method_parameter(raw_parameters);
-        let expected_parameters =
-            vec![super::Parameter::new("self".to_string(), "str".to_string())];
+        let expected_parameters = vec![
+            super::Parameter::new("a".to_string(), "None".to_string())
+                .with_kind(ParameterKind::PositionalOnly),
+            super::Parameter::new("args".to_string(), "None".to_string())
+                .with_kind(ParameterKind::VarPositional),
+            super::Parameter::new("key".to_string(), "int".to_string())
+                .with_kind(ParameterKind::KeywordOnly)
+                .with_default(Some("1".to_string())),
+        ];
         assert_eq!(parsed_parameters, expected_parameters);
     }
 
     #[test]
     fn test_parse_method_params_no_params() {
-        let raw_parameters = vec!["".to_string()];
-        let parsed_parameters = super::parse_method_parameter(raw_parameters);
+        let parsed_parameters = super::parse_method_parameter(vec![]);
         let expected_parameters = vec![];
         assert_eq!(parsed_parameters, expected_parameters);
     }
 
     #[test]
     fn test_parse_method_ok() {
-        let raw_methods = vec![
-            (
-                "_this_method".to_string(),
-                "self||None,xvalue||int".to_string(),
-                "None".to_string(),
-            ),
-            (
-                "another_method".to_string(),
-                "self||None".to_string(),
-                "str".to_string(),
-            ),
-        ];
+        let raw_methods = serde_json::from_str(
+            r#"[
+                {
+                    "name": "_this_method",
+                    "parameters": [
+                        {"name": "self", "annotation": null},
+                        {"name": "xvalue", "annotation": "int"}
+                    ],
+                    "output": null
+                },
+                {
+                    "name": "another_method",
+                    "parameters": [{"name": "self", "annotation": null}],
+                    "output": "str"
+                }
+            ]"#,
+        )
+        .unwrap();
         let parsed_methods = super::parse_method(raw_methods);
         let expected_methods = vec![
             super::Method {
@@ -130,6 +350,7 @@ mod tests {
                     super::Parameter::new("xvalue".to_string(), "int".to_string()),
                 ],
                 output: "None".to_string(),
+                ..Default::default()
             },
             super::Method {
                 name: "another_method".to_string(),
@@ -138,103 +359,205 @@ mod tests {
                     "Self".to_string(),
                 )],
                 output: "str".to_string(),
+                ..Default::default()
             },
         ];
         assert_eq!(parsed_methods, expected_methods);
     }
 
     #[test]
-    fn test_parse_method_with_wrong_parameters() {
-        let raw_methods = vec![
-            (
-                "_this_method".to_string(),
-                "self||None,xvalue||ThisIsAClass".to_string(),
-                "None".to_string(),
-            ),
-            (
-                "another_method".to_string(),
-                "self||None".to_string(),
-                "str".to_string(),
-            ),
-            (
-                "wrong_method".to_string(),
-                "self||None,xvalue".to_string(),
-                "str".to_string(),
-            ),
-        ];
+    fn test_parse_method_with_tag_like_values() {
+        // Values that used to break the regex based protocol
+        let raw_methods = serde_json::from_str(
+            r#"[{
+                "name": "tricky",
+                "parameters": [{"name": "xvalue", "annotation": "Dict<Output> x, y"}],
+                "output": "<Args> [a, b]"
+            }]"#,
+        )
+        .unwrap();
         let parsed_methods = super::parse_method(raw_methods);
-        let expected_methods = vec![
-            super::Method {
-                name: "_this_method".to_string(),
-                parameters: vec![
-                    super::Parameter::new("self".to_string(), "Self".to_string()),
-                    super::Parameter::new("xvalue".to_string(), "ThisIsAClass".to_string()),
-                ],
-                output: "None".to_string(),
-            },
-            super::Method {
-                name: "another_method".to_string(),
-                parameters: vec![super::Parameter::new(
-                    "self".to_string(),
-                    "Self".to_string(),
-                )],
-                output: "str".to_string(),
-            },
-            super::Method {
-                name: "wrong_method".to_string(),
-                parameters: vec![super::Parameter::new(
-                    "self".to_string(),
-                    "Self".to_string(),
-                )],
-                output: "str".to_string(),
-            },
-        ];
+        let expected_methods = vec![super::Method {
+            name: "tricky".to_string(),
+            parameters: vec![super::Parameter::new(
+                "xvalue".to_string(),
+                "Dict<Output> x, y".to_string(),
+            )],
+            output: "<Args> [a, b]".to_string(),
+            ..Default::default()
+        }];
         assert_eq!(parsed_methods, expected_methods);
     }
 
     #[test]
     fn test_parse_python_class() {
-        let raw_methods = vec![
-            (
-                "_this_method".to_string(),
-                "self||None,xvalue||int".to_string(),
-                "None".to_string(),
-            ),
-            (
-                "another_method".to_string(),
-                "self||None".to_string(),
-                "str".to_string(),
-            ),
-        ];
-        let parsed_class = super::parse_class(
-            "MyClass".to_string(),
-            raw_methods,
-            "This is a docstring".to_string(),
-            vec!["MyParentClass".to_string()],
-        );
+        let output = br#"{
+            "version": 13,
+            "class": {
+                "name": "MyClass",
+                "qualified_name": "MyClass",
+                "docstring": "This is a docstring <Output> x",
+                "inheritance": ["MyParentClass[int]"],
+                "keywords": [{"name": "metaclass", "value": "ABCMeta"}],
+                "attributes": [
+                    {"name": "x", "annotation": "int", "default": "0", "scope": "class", "method": null},
+                    {"name": "y", "annotation": null, "default": "x", "scope": "instance", "method": "__init__"}
+                ],
+                "methods": [{
+                    "name": "another_method",
+                    "parameters": [{"name": "self", "annotation": null}],
+                    "output": "str",
+                    "location": {"file": "my.py", "line": 4, "column": 4, "end_line": 5, "end_column": 20}
+                }]
+            }
+        }"#;
+        let parsed_class = super::document_class(super::parse_object_document(output).unwrap());
         let expected_class = super::PythonClass {
             name: "MyClass".to_string(),
-            methods: vec![
-                super::Method {
-                    name: "_this_method".to_string(),
-                    parameters: vec![
-                        super::Parameter::new("self".to_string(), "Self".to_string()),
-                        super::Parameter::new("xvalue".to_string(), "int".to_string()),
-                    ],
-                    output: "None".to_string(),
+            methods: vec![super::Method {
+                name: "another_method".to_string(),
+                parameters: vec![super::Parameter::new(
+                    "self".to_string(),
+                    "Self".to_string(),
+                )],
+                output: "str".to_string(),
+                location: Some(super::Location {
+                    file: "my.py".to_string(),
+                    line: 4,
+                    column: 4,
+                    end_line: 5,
+                    end_column: 20,
+                    cell: None,
+                }),
+                ..Default::default()
+            }],
+            docstring: "This is a docstring <Output> x".to_string(),
+            inheritance: vec!["MyParentClass[int]".to_string()],
+            keywords: vec![super::Keyword {
+                name: Some("metaclass".to_string()),
+                value: "ABCMeta".to_string(),
+            }],
+            attributes: vec![
+                super::Attribute {
+                    name: "x".to_string(),
+                    annotation: Some("int".to_string()),
+                    default: Some("0".to_string()),
+                    scope: AttributeScope::Class,
+                    method: None,
+                    location: None,
+                    type_source: TypeSource::Implementation,
                 },
-                super::Method {
-                    name: "another_method".to_string(),
-                    parameters: vec![super::Parameter::new(
-                        "self".to_string(),
-                        "Self".to_string(),
-                    )],
-                    output: "str".to_string(),
+                super::Attribute {
+                    name: "y".to_string(),
+                    annotation: None,
+                    default: Some("x".to_string()),
+                    scope: AttributeScope::Instance,
+                    method: Some("__init__".to_string()),
+                    location: None,
+                    type_source: TypeSource::Implementation,
                 },
             ],
-            docstring: "This is a docstring".to_string(),
-            inheritance: vec!["MyParentClass".to_string()],
+            qualified_name: "MyClass".to_string(),
+            nested_classes: vec![],
+            ..Default::default()
         };
-        assert_eq!(parsed_class, expected_class);
+        assert_eq!(parsed_class, Some(expected_class));
+    }
+
+    #[test]
+    fn test_parse_class_not_found() {
+        let output = br#"{"version": 13, "class": null}"#;
+        let document = super::parse_object_document(output).unwrap();
+        assert_eq!(super::document_class(document), None);
+    }
+
+    #[test]
+    fn test_parse_skipped_object_document() {
+        let output = br#"{"version": 13, "skipped": {
+            "file": "./bad.py", "line": 2, "cell": null, "error": "'(' was never closed"
+        }}"#;
+        let document = super::parse_object_document(output).unwrap();
+        assert_eq!(document.skipped.as_ref().unwrap().place(), "./bad.py:2");
+        assert_eq!(super::document_class(document), None);
+    }
+
+    #[test]
+    fn test_parse_function_document() {
+        let output = br#"{"version": 13, "class": null, "function": {
+            "name": "compute",
+            "parameters": [{"name": "self", "annotation": null, "kind": "regular", "default": null}],
+            "output": "int",
+            "decorators": [],
+            "is_async": false,
+            "is_generator": false
+        }}"#;
+        let document = super::parse_object_document(output).unwrap();
+        let function = super::document_function(document).unwrap();
+        assert_eq!(function.name, "compute");
+        assert_eq!(function.output, "int");
+        // A function parameter named `self` is not a receiver
+        assert_eq!(function.parameters[0].static_type, "None");
+    }
+
+    #[test]
+    fn test_parse_project_definitions() {
+        let output = br#"{"version": 13, "definitions": [
+            {"name": "God", "file": "./god.py"},
+            {"name": "pray", "file": "./god.py", "kind": "function"},
+            {"name": "MAX_GODS", "file": "./god.py", "kind": "constant"}
+        ], "skipped": [
+            {"file": "./legacy.py", "line": 3, "cell": null, "error": "invalid syntax"}
+        ]}"#;
+        let scan = super::parse_project(output).unwrap();
+        assert_eq!(scan.skipped[0].place(), "./legacy.py:3");
+        let kinds = scan
+            .definitions
+            .into_iter()
+            .map(|definition| definition.kind)
+            .collect::<Vec<DefinitionKind>>();
+        assert_eq!(
+            kinds,
+            vec![
+                DefinitionKind::Class,
+                DefinitionKind::Function,
+                DefinitionKind::Constant
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_unsupported_version() {
+        let output = br#"{"version": 99, "definitions": []}"#;
+        assert_eq!(
+            super::parse_project(output),
+            Err(ProtocolError::UnsupportedVersion(99))
+        );
+    }
+
+    #[test]
+    fn test_parse_script_version() {
+        let script_version = include_str!("scripts/common.py")
+            .lines()
+            .find_map(|line| line.strip_prefix("SCHEMA_VERSION = "))
+            .and_then(|version| version.parse::<u32>().ok());
+        assert_eq!(script_version, Some(super::SCHEMA_VERSION));
+
+        let newer = format!(
+            r#"{{"version": {}, "definitions": []}}"#,
+            super::SCHEMA_VERSION + 1
+        );
+        assert_eq!(
+            super::parse_project(newer.as_bytes()),
+            Err(ProtocolError::UnsupportedVersion(super::SCHEMA_VERSION + 1))
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_document() {
+        let output = b"<Class> God, <File> ./god.py";
+        assert!(matches!(
+            super::parse_project(output),
+            Err(ProtocolError::InvalidDocument(_))
+        ));
     }
 }
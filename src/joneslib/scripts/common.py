@@ -0,0 +1,128 @@
+"""Helpers shared by the jones scripts, loaded once by the worker as the
+jones_common module.
+"""
+import ast
+import collections
+import io
+import json
+import os
+import re
+import sys
+import tokenize
+
+# version of the documents the scripts return, bumped together with
+# SCHEMA_VERSION in parser.rs
+SCHEMA_VERSION = 13
+
+def is_escape(line):
+    line = line.lstrip()
+    if line.startswith(("%", "!")):
+        return True
+    targets, equals, value = line.partition("=")
+    return bool(
+        equals and re.match(r"^[\w, ]+$", targets) and targets.strip()
+        and value.lstrip().startswith(("%", "!"))
+    )
+
+def blank_escapes(source):
+    # only a line starting a logical line can be an escape, not one which
+    # continues brackets, a string or a backslash
+    depth = 0
+    string = None
+    backslash = False
+    for line in source.split("\n"):
+        if not (depth or string or backslash) and is_escape(line):
+            yield ""
+            continue
+        yield line
+        line = line.rstrip("\r")
+        backslash = False
+        index = 0
+        while index < len(line):
+            character = line[index]
+            last = index + 1 == len(line)
+            if string is not None:
+                if character == "\\":
+                    backslash = last
+                    index += 1
+                elif line.startswith(string, index):
+                    index += len(string) - 1
+                    string = None
+            elif character == "#":
+                break
+            elif character in "'\"":
+                string = character * 3 if line.startswith(character * 3, index) else character
+                index += len(string) - 1
+            elif character in "([{":
+                depth += 1
+            elif character in ")]}":
+                depth = max(depth - 1, 0)
+            elif character == "\\":
+                backslash = last
+            index += 1
+        if string is not None and len(string) == 1 and not backslash:
+            string = None
+
+def read_text(path):
+    with open(path, "rb") as file:
+        content = file.read()
+    try:
+        encoding = tokenize.detect_encoding(io.BytesIO(content).readline)[0]
+    except SyntaxError:
+        sys.stderr.write(path + ": unknown source encoding, read as UTF-8\n")
+        encoding = "utf-8"
+    try:
+        return content.decode(encoding)
+    except UnicodeDecodeError:
+        sys.stderr.write(path + ": invalid bytes replaced with U+FFFD\n")
+        return content.decode(encoding, errors="replace")
+
+def read_source(path):
+    content = read_text(path)
+    if not path.endswith(".ipynb"):
+        return content, []
+    lines = []
+    cells = []
+    for index, cell in enumerate(json.loads(content).get("cells", [])):
+        if cell.get("cell_type") != "code":
+            continue
+        source = cell.get("source", "")
+        if isinstance(source, list):
+            source = "".join(source)
+        if source.lstrip().startswith("%%"):
+            continue
+        cells.append((index + 1, len(lines) + 1))
+        lines.extend(blank_escapes(source))
+    return "\n".join(lines) + "\n", cells
+
+def cell_line(cells, line):
+    for cell, first_line in reversed(cells):
+        if first_line <= line:
+            return cell, line - first_line + 1
+    return None, line
+
+def display_path(path):
+    # paths which are not UTF-8 could not be written as JSON
+    return os.fsencode(path).decode("utf-8", "replace")
+
+def skipped_file(path, error, cells):
+    line = getattr(error, "lineno", None)
+    cell = None
+    if line is not None:
+        cell, line = cell_line(cells, line)
+    message = error.msg if isinstance(error, SyntaxError) else str(error)
+    return {"file": display_path(path), "line": line, "cell": cell, "error": message}
+
+def walk_classes(tree):
+    todo = collections.deque(("", child) for child in ast.iter_child_nodes(tree))
+    while todo:
+        scope, node = todo.popleft()
+        if isinstance(node, ast.ClassDef):
+            qualname = scope + "." + node.name if scope else node.name
+            todo.extend((qualname, child) for child in ast.iter_child_nodes(node))
+            yield qualname, node
+        elif isinstance(node, (ast.FunctionDef, ast.AsyncFunctionDef)):
+            local_scope = (scope + "." + node.name if scope else node.name) + ".<locals>"
+            todo.extend((local_scope, child) for child in ast.iter_child_nodes(node))
+        else:
+            todo.extend((scope, child) for child in ast.iter_child_nodes(node))
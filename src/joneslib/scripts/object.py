@@ -0,0 +1,290 @@
+"""Extracts a class, or a top-level function, from a Python file for jones.
+
+Run by the jones worker as main([FILE, MODULE_PATH, NAME]).
+
+NAME can be qualified, and prefixed by (the end of) MODULE_PATH. The class
+best matching it and the function matching it exactly are returned as a JSON
+document of the schema version declared in jones_common. A file which cannot
+be parsed is returned as skipped instead. Without a matching class, the call
+assigned to NAME at the top level is returned as a possible functional enum.
+"""
+import ast
+import io
+import tokenize
+
+from jones_common import (
+    SCHEMA_VERSION, cell_line, display_path, read_source, skipped_file, walk_classes,
+)
+
+# the file being read, set by main for the helpers below
+FILE_NAME = None
+CELLS = []
+source = ""
+
+def location(node):
+    line = node.lineno
+    end_line = getattr(node, "end_lineno", None) or node.lineno
+    cell, line = cell_line(CELLS, line)
+    end_line = cell_line(CELLS, end_line)[1]
+    return {
+        "file": display_path(FILE_NAME),
+        "line": line,
+        "column": node.col_offset,
+        "end_line": end_line,
+        "end_column": getattr(node, "end_col_offset", None) or node.col_offset,
+        "cell": cell,
+    }
+
+def unparse(node):
+    if isinstance(node, ast.Name):
+        return node.id
+    if isinstance(node, ast.Attribute):
+        return unparse(node.value) + "." + node.attr
+    if isinstance(node, ast.Subscript):
+        inner = node.slice.value if isinstance(node.slice, getattr(ast, "Index", ())) else node.slice
+        if isinstance(inner, ast.Tuple):
+            return unparse(node.value) + "[" + ", ".join(unparse(e) for e in inner.elts) + "]"
+        return unparse(node.value) + "[" + unparse(inner) + "]"
+    if isinstance(node, (ast.Tuple, ast.List)):
+        elements = ", ".join(unparse(e) for e in node.elts)
+        return "[" + elements + "]" if isinstance(node, ast.List) else "(" + elements + ")"
+    if isinstance(node, ast.BinOp) and isinstance(node.op, ast.BitOr):
+        return unparse(node.left) + " | " + unparse(node.right)
+    if isinstance(node, ast.Constant):
+        return "..." if node.value is Ellipsis else repr(node.value)
+    if isinstance(node, ast.Call):
+        return unparse(node.func) + "(" + ", ".join(unparse(a) for a in node.args) + ")"
+    return "..."
+
+def render(annotation):
+    if annotation is None:
+        return None
+    segment = None
+    if hasattr(ast, "get_source_segment"):
+        segment = ast.get_source_segment(source, annotation)
+    if segment is None:
+        return unparse(annotation)
+    if "\n" in segment:
+        try:
+            return join_lines(segment)
+        except (tokenize.TokenError, SyntaxError):
+            return unparse(annotation)
+    return segment
+
+def join_lines(segment):
+    # drops the comments and line breaks, but not what strings hold, spacing
+    # the tokens as the native backend does
+    skipped = (tokenize.COMMENT, tokenize.NL, tokenize.NEWLINE, tokenize.INDENT,
+               tokenize.DEDENT, tokenize.ENDMARKER)
+    text = ""
+    previous = None
+    for token in tokenize.generate_tokens(io.StringIO(segment).readline):
+        if token.type in skipped:
+            continue
+        if previous is not None:
+            same_line = previous.end[0] == token.start[0]
+            separated = token.start != previous.end
+            tight = previous.string in ("(", "[", "{") or token.string in (")", "]", "}", ",")
+            if separated and (same_line or not tight):
+                text += " "
+        text += token.string
+        previous = token
+    return text
+
+def is_generator(node_method):
+    nodes = list(node_method.body)
+    while nodes:
+        node = nodes.pop()
+        if isinstance(node, (ast.Yield, ast.YieldFrom)):
+            return True
+        if isinstance(node, (ast.FunctionDef, ast.AsyncFunctionDef, ast.ClassDef, ast.Lambda)):
+            continue
+        nodes.extend(ast.iter_child_nodes(node))
+    return False
+
+def get_parameter(arg, kind, default=None):
+    return {
+        "name": arg.arg,
+        "annotation": render(arg.annotation),
+        "kind": kind,
+        "default": render(default),
+        "location": location(arg),
+    }
+
+def get_method(node_method):
+    arguments = node_method.args
+    positional = [(arg, "positional_only") for arg in getattr(arguments, "posonlyargs", [])]
+    positional += [(arg, "regular") for arg in arguments.args]
+    defaults = [None] * (len(positional) - len(arguments.defaults)) + arguments.defaults
+
+    parameters = []
+    for (arg, kind), default in zip(positional, defaults):
+        parameters.append(get_parameter(arg, kind, default))
+    if arguments.vararg is not None:
+        parameters.append(get_parameter(arguments.vararg, "var_positional"))
+    for arg, default in zip(arguments.kwonlyargs, arguments.kw_defaults):
+        parameters.append(get_parameter(arg, "keyword_only", default))
+    if arguments.kwarg is not None:
+        parameters.append(get_parameter(arguments.kwarg, "var_keyword"))
+
+    return {
+        "name": node_method.name,
+        "parameters": parameters,
+        "output": render(node_method.returns),
+        "decorators": [render(decorator) for decorator in node_method.decorator_list],
+        "is_async": isinstance(node_method, ast.AsyncFunctionDef),
+        "is_generator": is_generator(node_method),
+        "docstring": ast.get_docstring(node_method),
+        "location": location(node_method),
+    }
+
+def target_names(target):
+    if isinstance(target, ast.Starred):
+        return target_names(target.value)
+    if isinstance(target, (ast.Tuple, ast.List)):
+        return [name for element in target.elts for name in target_names(element)]
+    return [target]
+
+def get_attribute(name, node, scope, method=None):
+    unpacking = isinstance(node, ast.Assign) and any(
+        isinstance(target, (ast.Tuple, ast.List)) for target in node.targets
+    )
+    return {
+        "name": name,
+        "annotation": render(getattr(node, "annotation", None)),
+        "default": None if unpacking else render(node.value),
+        "scope": scope,
+        "method": method,
+        "location": location(node),
+    }
+
+def assignment_targets(node):
+    if isinstance(node, ast.Assign):
+        return [name for target in node.targets for name in target_names(target)]
+    if isinstance(node, ast.AnnAssign):
+        return [node.target]
+    return []
+
+def method_assignments(nodes):
+    for node in nodes:
+        if isinstance(node, (ast.FunctionDef, ast.AsyncFunctionDef, ast.ClassDef, ast.Lambda)):
+            continue
+        if isinstance(node, (ast.Assign, ast.AnnAssign)):
+            yield node
+        yield from method_assignments(ast.iter_child_nodes(node))
+
+def get_attributes(node_class):
+    attributes = []
+    seen = set()
+
+    def add(name, node, scope, method=None):
+        if name not in seen:
+            seen.add(name)
+            attributes.append(get_attribute(name, node, scope, method))
+
+    for node in node_class.body:
+        for target in assignment_targets(node):
+            if isinstance(target, ast.Name):
+                add(target.id, node, "class")
+
+    methods = [
+        m for m in node_class.body
+        if isinstance(m, (ast.FunctionDef, ast.AsyncFunctionDef))
+    ]
+    methods.sort(key=lambda m: m.name != "__init__")
+    for m in methods:
+        decorators = [render(d).split("(")[0].split(".")[-1] for d in m.decorator_list]
+        if "staticmethod" in decorators or "classmethod" in decorators:
+            continue
+        positional = getattr(m.args, "posonlyargs", []) + m.args.args
+        if not positional:
+            continue
+        receiver = positional[0].arg
+        for node in method_assignments(m.body):
+            for target in assignment_targets(node):
+                if (
+                    isinstance(target, ast.Attribute)
+                    and isinstance(target.value, ast.Name)
+                    and target.value.id == receiver
+                ):
+                    add(target.attr, node, "instance", m.name)
+
+    return attributes
+
+def get_class(node, qualname):
+    return {
+        "name": node.name,
+        "qualified_name": qualname,
+        "docstring": ast.get_docstring(node),
+        "inheritance": [render(base) for base in node.bases],
+        "decorators": [render(decorator) for decorator in node.decorator_list],
+        "keywords": [{"name": k.arg, "value": render(k.value)} for k in node.keywords],
+        "methods": [
+            get_method(m) for m in node.body
+            if isinstance(m, ast.FunctionDef) or isinstance(m, ast.AsyncFunctionDef)
+        ],
+        "attributes": get_attributes(node),
+        "nested_classes": [
+            get_class(child, qualname + "." + child.name)
+            for child in node.body if isinstance(child, ast.ClassDef)
+        ],
+        "location": location(node),
+    }
+
+def match_rank(requested, module_path, qualname):
+    if requested == qualname:
+        return 0
+    if requested.endswith("." + qualname):
+        prefix = requested[:-len(qualname) - 1]
+        if module_path == prefix or module_path.endswith("." + prefix):
+            return 0
+    if qualname.endswith("." + requested):
+        return 1
+    return None
+
+def get_functional_enum(tree, module_path, name):
+    for node in tree.body:
+        if not (isinstance(node, ast.Assign) and isinstance(node.value, ast.Call)):
+            continue
+        if len(node.targets) != 1 or not isinstance(node.targets[0], ast.Name):
+            continue
+        if match_rank(name, module_path, node.targets[0].id) == 0:
+            return {
+                "name": node.targets[0].id,
+                "expression": ast.get_source_segment(source, node.value),
+                "location": location(node),
+            }
+    return None
+
+def main(arguments):
+    global FILE_NAME, CELLS, source
+    FILE_NAME, module_path, name = arguments
+    CELLS = []
+    try:
+        source, CELLS = read_source(FILE_NAME)
+        tree = ast.parse(source)
+    except (SyntaxError, ValueError) as error:
+        return {"version": SCHEMA_VERSION, "skipped": skipped_file(FILE_NAME, error, CELLS)}
+
+    best = None
+    for qualname, node in walk_classes(tree):
+        rank = match_rank(name, module_path, qualname)
+        if rank is not None and (best is None or rank < best[0]):
+            best = (rank, qualname, node)
+
+    found_class = get_class(best[2], best[1]) if best is not None else None
+    functional_enum = None if best is not None else get_functional_enum(tree, module_path, name)
+
+    found_function = None
+    for node in tree.body:
+        if isinstance(node, (ast.FunctionDef, ast.AsyncFunctionDef)):
+            if match_rank(name, module_path, node.name) == 0:
+                found_function = get_method(node)
+                break
+
+    return {
+        "version": SCHEMA_VERSION,
+        "class": found_class,
+        "function": found_function,
+        "functional_enum": functional_enum,
+    }
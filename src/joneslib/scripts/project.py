@@ -0,0 +1,93 @@
+"""Lists the classes, functions and constants of a project for jones.
+
+Run by the jones worker as main([ENUM_BASES, FILE...]).
+
+ENUM_BASES is a comma separated list of the enum base classes. The definitions
+found in the FILEs, and the files which could not be parsed, are returned as a
+JSON document of the schema version declared in jones_common.
+"""
+import ast
+import re
+
+from jones_common import SCHEMA_VERSION, display_path, read_source, skipped_file, walk_classes
+
+# the enum base classes, set by main
+ENUM_BASES = set()
+
+def is_functional_enum(value):
+    if not isinstance(value, ast.Call):
+        return False
+    func = value.func
+    name = func.attr if isinstance(func, ast.Attribute) else getattr(func, "id", None)
+    arguments = value.args
+    return (
+        name in ENUM_BASES and bool(arguments)
+        and isinstance(arguments[0], ast.Constant) and isinstance(arguments[0].value, str)
+    )
+
+def is_final(annotation):
+    if isinstance(annotation, ast.Subscript):
+        annotation = annotation.value
+    return getattr(annotation, "attr", getattr(annotation, "id", None)) == "Final"
+
+def assigned_names(node):
+    targets = node.targets if isinstance(node, ast.Assign) else [node.target]
+    names = []
+    for target in targets:
+        if isinstance(target, (ast.Tuple, ast.List)):
+            if len(target.elts) != 1:
+                return None
+            target = target.elts[0]
+        if isinstance(target, ast.Name):
+            names.append(target.id)
+    return names
+
+def scan_file(path, definitions, skipped):
+    cells = []
+    try:
+        source, cells = read_source(path)
+        definitions.extend(module_definitions(ast.parse(source), display_path(path)))
+    except Exception as error:
+        skipped.append(skipped_file(path, error, cells))
+
+def module_definitions(tree, file_name):
+    definitions = []
+
+    def add(name, kind):
+        definitions.append({"name": name, "file": file_name, "kind": kind})
+
+    for qualname, node in walk_classes(tree):
+        add(qualname, "class")
+    assignments = []
+    for node in tree.body:
+        if isinstance(node, (ast.Assign, ast.AnnAssign)) and node.value is not None:
+            names = assigned_names(node)
+            if names is not None:
+                assignments.append((node, names))
+    for node, names in assignments:
+        targets = node.targets if isinstance(node, ast.Assign) else [node.target]
+        if is_functional_enum(node.value) and len(targets) == 1:
+            for name in names:
+                add(name, "class")
+    for node in tree.body:
+        if isinstance(node, (ast.FunctionDef, ast.AsyncFunctionDef)):
+            add(node.name, "function")
+    for node, names in assignments:
+        if is_functional_enum(node.value):
+            continue
+        final = is_final(getattr(node, "annotation", None))
+        for name in names:
+            if final or re.match(r"_*[A-Z][A-Z0-9_]*$", name):
+                add(name, "constant")
+    return definitions
+
+def main(arguments):
+    global ENUM_BASES
+    ENUM_BASES = set(arguments[0].split(","))
+
+    definitions = []
+    skipped = []
+    for path in arguments[1:]:
+        scan_file(path, definitions, skipped)
+
+    return {"version": SCHEMA_VERSION, "definitions": definitions, "skipped": skipped}
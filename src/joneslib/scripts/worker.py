@@ -0,0 +1,52 @@
+"""Serves the jones scripts from a single, long-lived interpreter.
+
+The first line read from stdin holds the sources of the helper modules the
+scripts import, and of the scripts, by name. Each line after it is a request, {"id": ..., "script": ..., "arguments": [...]}, which
+gets answered by a single line on stdout, {"id": ..., "document": ...}, or
+{"id": ..., "error": ...} when the script failed. Anything the scripts print
+goes to stderr, so that stdout only carries the answers.
+"""
+import json
+import os
+import sys
+import traceback
+import types
+
+def load_modules(sources):
+    for name, source in sources.items():
+        module = types.ModuleType(name)
+        exec(compile(source, name + ".py", "exec"), module.__dict__)
+        sys.modules[name] = module
+
+def load_scripts(sources):
+    scripts = {}
+    for name, source in sources.items():
+        namespace = {"__name__": "jones_" + name}
+        exec(compile(source, name + ".py", "exec"), namespace)
+        scripts[name] = namespace["main"]
+    return scripts
+
+def argument(value):
+    # paths which are not UTF-8 come as their bytes
+    return os.fsdecode(bytes(value)) if isinstance(value, list) else value
+
+def serve():
+    answers = sys.stdout
+    sys.stdout = sys.stderr
+    # read as bytes, json takes them as UTF-8 whatever the locale
+    requests = sys.stdin.buffer
+    sources = json.loads(requests.readline())
+    load_modules(sources["modules"])
+    scripts = load_scripts(sources["scripts"])
+    for line in requests:
+        request = json.loads(line)
+        response = {"id": request["id"]}
+        try:
+            arguments = [argument(value) for value in request["arguments"]]
+            response["document"] = scripts[request["script"]](arguments)
+        except Exception:
+            response["error"] = traceback.format_exc()
+        answers.write(json.dumps(response) + "\n")
+        answers.flush()
+
+serve()
@@ -0,0 +1,233 @@
+/*
+JonesCLI
+
+Author: Vlad Nedelcu
+Date: Jul 2021
+License: MIT
+
+Copyright 2021 Vlad Nedelcu
+*/
+use std::path::{Path, PathBuf};
+
+use super::objects::{Attribute, Method, PythonClass, TypeSource};
+use super::{PYTHON_EXTENSION, STUB_EXTENSION};
+
+const MISSING_OUTPUT: &str = "None";
+const MISSING_DOCSTRING: &str = "None";
+const OVERLOAD_DECORATOR: &str = "overload";
+
+/// The `.pyi` stub sitting next to a module, `foo.pyi` for `foo.py`.
+pub fn stub_path(file_path: &Path) -> Option<PathBuf> {
+    sibling_with_extension(file_path, PYTHON_EXTENSION, STUB_EXTENSION)
+}
+
+/// The module a `.pyi` stub describes, `foo.py` for `foo.pyi`.
+pub fn implementation_path(file_path: &Path) -> Option<PathBuf> {
+    sibling_with_extension(file_path, STUB_EXTENSION, PYTHON_EXTENSION)
+}
+
+pub fn is_stub(file_path: &Path) -> bool {
+    file_path
+        .extension()
+        .is_some_and(|extension| extension == STUB_EXTENSION)
+}
+
+fn sibling_with_extension(file_path: &Path, from: &str, to: &str) -> Option<PathBuf> {
+    if file_path
+        .extension()
+        .is_none_or(|extension| extension != from)
+    {
+        return None;
+    }
+    let sibling = file_path.with_extension(to);
+    sibling.is_file().then_some(sibling)
+}
+
+/// Merges the types declared by a stub into the class read from the module.
+/// Members only the stub declares, as for C extensions, are added as well.
+pub fn merge_class(mut class: PythonClass, stub: PythonClass, stub_path: &Path) -> PythonClass {
+    class.stub = Some(stub_path.display().to_string());
+    merge_class_members(&mut class, stub);
+    class
+}
+
+fn merge_class_members(class: &mut PythonClass, stub: PythonClass) {
+    if class.docstring == MISSING_DOCSTRING {
+        class.docstring = stub.docstring;
+    }
+
+    // stub-only overloads are all kept, so only look among the implemented methods
+    let implemented = class.methods.len();
+    for stub_method in stub.methods {
+        let position = class.methods[..implemented].iter().position(|method| {
+            method.name == stub_method.name && method.kind() == stub_method.kind()
+        });
+        match position {
+            Some(position) => merge_method_types(&mut class.methods[position], stub_method),
+            None => class.methods.push(from_stub(stub_method)),
+        }
+    }
+
+    for stub_attribute in stub.attributes {
+        let position = class
+            .attributes
+            .iter()
+            .position(|attribute| attribute.name == stub_attribute.name);
+        match position {
+            Some(position) => merge_attribute_type(&mut class.attributes[position], stub_attribute),
+            None => {
+                let mut attribute = stub_attribute;
+                if attribute.annotation.is_some() {
+                    attribute.type_source = TypeSource::Stub;
+                }
+                class.attributes.push(attribute);
+            }
+        }
+    }
+
+    for stub_nested in stub.nested_classes {
+        let nested = class
+            .nested_classes
+            .iter_mut()
+            .find(|nested| nested.qualified_name == stub_nested.qualified_name);
+        match nested {
+            Some(nested) => merge_class_members(nested, stub_nested),
+            None => class.nested_classes.push(stub_nested),
+        }
+    }
+}
+
+/// Takes the parameter and return types annotated in the stub version of a
+/// function, keeping the rest of the implementation.
+pub fn merge_method_types(method: &mut Method, stub: Method) {
+    if is_overload(&stub) {
+        return;
+    }
+    if method.docstring.is_none() {
+        method.docstring = stub.docstring;
+    }
+    if stub.output != MISSING_OUTPUT {
+        method.output = stub.output;
+        method.output_source = TypeSource::Stub;
+    }
+    for stub_parameter in stub.parameters {
+        let parameter = method
+            .parameters
+            .iter_mut()
+            .find(|parameter| parameter.name == stub_parameter.name);
+        if let Some(parameter) = parameter {
+            if stub_parameter.is_annotated() {
+                parameter.static_type = stub_parameter.static_type;
+                parameter.type_source = TypeSource::Stub;
+            }
+        }
+    }
+}
+
+fn merge_attribute_type(attribute: &mut Attribute, stub: Attribute) {
+    if stub.annotation.is_some() {
+        attribute.annotation = stub.annotation;
+        attribute.type_source = TypeSource::Stub;
+    }
+}
+
+/// A method only declared by the stub, all its types coming from it.
+fn from_stub(mut method: Method) -> Method {
+    if method.output != MISSING_OUTPUT {
+        method.output_source = TypeSource::Stub;
+    }
+    for parameter in method
+        .parameters
+        .iter_mut()
+        .filter(|parameter| parameter.is_annotated())
+    {
+        parameter.type_source = TypeSource::Stub;
+    }
+    method
+}
+
+/// Overloads each describe one way of calling the function, so none of them
+/// types the implementation on its own.
+fn is_overload(method: &Method) -> bool {
+    method
+        .decorators
+        .iter()
+        .any(|decorator| decorator.rsplit('.').next() == Some(OVERLOAD_DECORATOR))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{merge_class, stub_path};
+    use crate::joneslib::native::load_python_object;
+    use crate::joneslib::objects::TypeSource;
+    use std::fs;
+    use std::path::Path;
+
+    static IMPLEMENTATION: &str = "
+class Codec:
+    level = 3
+
+    def encode(self, data, *, strict=False):
+        \"\"\"Encodes the data.\"\"\"
+        return data
+
+    def decode(self, data: bytes) -> str:
+        return data.decode()
+";
+    static STUB: &str = "
+from typing import overload
+
+class Codec:
+    \"\"\"Encodes and decodes payloads.\"\"\"
+    level: int
+
+    def encode(self, data: bytes, *, strict: bool = ...) -> bytes: ...
+
+    @overload
+    def decode(self, data: str) -> str: ...
+    @overload
+    def decode(self, data: bytes) -> str: ...
+
+    def reset(self) -> None: ...
+";
+
+    #[test]
+    fn test_merge_stub_types() {
+        let test_dir = std::env::temp_dir().join("jones_test_merge_stub_types");
+        fs::create_dir_all(&test_dir).unwrap();
+        let file_path = test_dir.join("codec.py");
+        fs::write(&file_path, IMPLEMENTATION).unwrap();
+        fs::write(test_dir.join("codec.pyi"), STUB).unwrap();
+
+        let stub_file = stub_path(&file_path).unwrap();
+        assert_eq!(stub_path(&stub_file), None);
+        let class = load_python_object(&file_path, "codec", "Codec").unwrap();
+        let stub = load_python_object(&stub_file, "codec", "Codec").unwrap();
+        let merged = merge_class(class, stub, &stub_file);
+
+        assert_eq!(merged.stub, Some(stub_file.display().to_string()));
+        // the class docstring only the stub has is taken
+        assert_eq!(merged.docstring, "Encodes and decodes payloads.");
+        let encode = &merged.methods[0];
+        assert_eq!(encode.docstring.as_deref(), Some("Encodes the data."));
+        assert_eq!(encode.output, "bytes");
+        assert_eq!(encode.output_source, TypeSource::Stub);
+        assert_eq!(encode.parameters[1].static_type, "bytes");
+        assert_eq!(encode.parameters[1].type_source, TypeSource::Stub);
+        assert_eq!(encode.parameters[2].default.as_deref(), Some("False"));
+        // the receiver is typed the same way on both sides
+        assert_eq!(encode.parameters[0].type_source, TypeSource::Implementation);
+        // overloads leave the implementation types alone
+        let decode = &merged.methods[1];
+        assert_eq!(decode.parameters[1].static_type, "bytes");
+        assert_eq!(decode.output_source, TypeSource::Implementation);
+        assert_eq!(merged.methods[2].name, "reset");
+        assert_eq!(merged.methods.len(), 3);
+        assert_eq!(merged.attributes[0].annotation.as_deref(), Some("int"));
+        assert_eq!(merged.attributes[0].default.as_deref(), Some("3"));
+        assert_eq!(merged.attributes[0].type_source, TypeSource::Stub);
+
+        fs::remove_dir_all(&test_dir).unwrap();
+        assert_eq!(stub_path(Path::new("missing.py")), None);
+    }
+}
@@ -0,0 +1,312 @@
+/*
+JonesCLI
+
+Author: Vlad Nedelcu
+Date: Jul 2021
+License: MIT
+
+Copyright 2021 Vlad Nedelcu
+*/
+use std::path::{Path, PathBuf};
+
+use globset::{GlobBuilder, GlobMatcher};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use walkdir::{DirEntry, WalkDir};
+
+use super::is_python_file;
+
+/// Directories holding tooling, dependencies or caches rather than project
+/// sources. They are never descended into, unless searched directly.
+pub const DEFAULT_EXCLUDES: [&str; 12] = [
+    ".git",
+    ".hg",
+    ".svn",
+    ".venv",
+    "venv",
+    "node_modules",
+    "site-packages",
+    "__pycache__",
+    ".mypy_cache",
+    ".pytest_cache",
+    ".tox",
+    ".nox",
+];
+/// Files listing ignored paths, the later ones taking precedence.
+const IGNORE_FILES: [&str; 2] = [".gitignore", ".ignore"];
+const REPOSITORY_DIR: &str = ".git";
+
+/// A glob given with `--include` or `--exclude`. Like in a `.gitignore`, one
+/// without a `/` matches names at any depth, others match the path relative
+/// to the searched directory.
+#[derive(Debug, Clone)]
+struct PathGlob {
+    matcher: GlobMatcher,
+    name_only: bool,
+}
+impl PathGlob {
+    fn new(glob: &str) -> Result<Self, globset::Error> {
+        let matcher = GlobBuilder::new(glob)
+            .literal_separator(true)
+            .build()?
+            .compile_matcher();
+        Ok(PathGlob {
+            matcher,
+            name_only: !glob.contains('/'),
+        })
+    }
+
+    fn is_match(&self, relative_path: &Path) -> bool {
+        if self.name_only {
+            relative_path
+                .file_name()
+                .is_some_and(|name| self.matcher.is_match(name))
+        } else {
+            self.matcher.is_match(relative_path)
+        }
+    }
+}
+
+/// Checks a glob given on the command line.
+pub fn validate_glob(glob: String) -> Result<(), String> {
+    PathGlob::new(&glob)
+        .map(|_| ())
+        .map_err(|error| error.to_string())
+}
+
+/// Picks the Python files of a project, honouring `.gitignore` and `.ignore`
+/// files, the default excludes and the `--include` and `--exclude` globs.
+#[derive(Debug, Clone, Default)]
+pub struct Traversal {
+    include: Vec<PathGlob>,
+    exclude: Vec<PathGlob>,
+}
+impl Traversal {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self, globset::Error> {
+        let compile = |globs: &[String]| {
+            globs
+                .iter()
+                .map(|glob| PathGlob::new(glob))
+                .collect::<Result<Vec<PathGlob>, globset::Error>>()
+        };
+        Ok(Traversal {
+            include: compile(include)?,
+            exclude: compile(exclude)?,
+        })
+    }
+
+    /// The Python files under a directory, the files of each directory
+    /// before its subdirectories, both by name. Symbolic links are followed,
+    /// loops among them reported and skipped. A file is taken as it is.
+    pub fn python_files(&self, path: &Path) -> Vec<PathBuf> {
+        if !path.is_dir() {
+            return vec![path.to_path_buf()];
+        }
+        let parent_ignores = ParentIgnores::new(path);
+        // the ignore files of the directories above the current entry
+        let mut ignores: Vec<(usize, Gitignore)> = Vec::new();
+
+        let walker = WalkDir::new(path)
+            .follow_links(true)
+            .sort_by(|first, second| {
+                (first.file_type().is_dir(), first.file_name())
+                    .cmp(&(second.file_type().is_dir(), second.file_name()))
+            })
+            .into_iter()
+            .filter_entry(|entry| {
+                ignores.retain(|(depth, _)| *depth < entry.depth());
+                let keep =
+                    entry.depth() == 0 || !self.is_excluded(path, entry, &ignores, &parent_ignores);
+                if keep && entry.file_type().is_dir() {
+                    if let Some(gitignore) = dir_ignores(entry.path()) {
+                        ignores.push((entry.depth(), gitignore));
+                    }
+                }
+                keep
+            });
+
+        let mut files = Vec::new();
+        for entry in walker {
+            match entry {
+                Ok(entry) => {
+                    if entry.file_type().is_file()
+                        && is_python_file(entry.path())
+                        && self.is_included(path, entry.path())
+                    {
+                        files.push(entry.into_path());
+                    }
+                }
+                Err(error) => eprintln!("Error occurred while walking the project: {}", error),
+            }
+        }
+        files
+    }
+
+    fn is_excluded(
+        &self,
+        root: &Path,
+        entry: &DirEntry,
+        ignores: &[(usize, Gitignore)],
+        parent_ignores: &ParentIgnores,
+    ) -> bool {
+        let is_dir = entry.file_type().is_dir();
+        if is_dir
+            && DEFAULT_EXCLUDES
+                .iter()
+                .any(|exclude| entry.file_name() == *exclude)
+        {
+            return true;
+        }
+        let relative_path = entry.path().strip_prefix(root).unwrap_or(entry.path());
+        if self.exclude.iter().any(|glob| glob.is_match(relative_path)) {
+            return true;
+        }
+
+        // the deepest ignore file with a matching rule decides
+        for (_, gitignore) in ignores.iter().rev() {
+            match gitignore.matched(entry.path(), is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+        parent_ignores.is_ignored(relative_path, is_dir)
+    }
+
+    fn is_included(&self, root: &Path, file_path: &Path) -> bool {
+        let relative_path = file_path.strip_prefix(root).unwrap_or(file_path);
+        self.include.is_empty() || self.include.iter().any(|glob| glob.is_match(relative_path))
+    }
+}
+
+/// The ignore files of a directory, when it has any.
+fn dir_ignores(dir: &Path) -> Option<Gitignore> {
+    let mut builder = GitignoreBuilder::new(dir);
+    let mut found = false;
+    for name in IGNORE_FILES.iter() {
+        let ignore_file = dir.join(name);
+        if !ignore_file.is_file() {
+            continue;
+        }
+        found = true;
+        if let Some(error) = builder.add(&ignore_file) {
+            eprintln!("{}: {}", ignore_file.display(), error);
+        }
+    }
+    if !found {
+        return None;
+    }
+    builder
+        .build()
+        .ok()
+        .filter(|gitignore| !gitignore.is_empty())
+}
+
+/// The ignore files of the directories above the searched one, up to the
+/// root of the git repository it is in. Outside of a repository there are
+/// none, the way git sees it.
+struct ParentIgnores {
+    root: PathBuf,
+    ignores: Vec<Gitignore>,
+}
+impl ParentIgnores {
+    fn new(root: &Path) -> Self {
+        let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+        let repository = root
+            .ancestors()
+            .find(|dir| dir.join(REPOSITORY_DIR).exists());
+        let ignores = match repository {
+            Some(repository) => root
+                .ancestors()
+                .skip(1)
+                .take_while(|dir| dir.starts_with(repository))
+                .filter_map(dir_ignores)
+                .collect(),
+            None => Vec::new(),
+        };
+        ParentIgnores { root, ignores }
+    }
+
+    fn is_ignored(&self, relative_path: &Path, is_dir: bool) -> bool {
+        let path = self.root.join(relative_path);
+        for gitignore in self.ignores.iter() {
+            match gitignore.matched(&path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{validate_glob, Traversal};
+    use std::fs;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_python_files() {
+        let project = std::env::temp_dir().join("jones_test_python_files");
+        for dir in [
+            "shop/legacy",
+            "shop/tests",
+            "node_modules/lib",
+            "build",
+            ".git",
+        ]
+        .iter()
+        {
+            fs::create_dir_all(project.join(dir)).unwrap();
+        }
+        for file in [
+            "setup.py",
+            "notes.txt",
+            "shop/models.py",
+            "shop/generated.py",
+            "shop/keep.py",
+            "shop/legacy/old.py",
+            "shop/tests/test_models.py",
+            "node_modules/lib/vendored.py",
+            "build/lib.py",
+        ]
+        .iter()
+        {
+            fs::write(project.join(file), "").unwrap();
+        }
+        fs::write(project.join(".gitignore"), "build/\ngenerated.py\n").unwrap();
+        fs::write(project.join("shop/.ignore"), "legacy\n").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&project, project.join("shop/loop")).unwrap();
+
+        let relative = |files: Vec<PathBuf>| {
+            files
+                .iter()
+                .map(|file| file.strip_prefix(&project).unwrap().display().to_string())
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(
+            relative(Traversal::default().python_files(&project)),
+            vec![
+                "setup.py",
+                "shop/keep.py",
+                "shop/models.py",
+                "shop/tests/test_models.py"
+            ]
+        );
+        let traversal = Traversal::new(&["shop/**".to_string()], &["tests".to_string()]).unwrap();
+        assert_eq!(
+            relative(traversal.python_files(&project)),
+            vec!["shop/keep.py", "shop/models.py"]
+        );
+        // a file is not filtered
+        let file = project.join("build/lib.py");
+        assert_eq!(traversal.python_files(&file), vec![file]);
+
+        assert!(validate_glob("src/**/*.py".to_string()).is_ok());
+        assert!(validate_glob("src/[a".to_string()).is_err());
+
+        fs::remove_dir_all(&project).unwrap();
+    }
+}
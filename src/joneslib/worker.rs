@@ -0,0 +1,346 @@
+/*
+JonesCLI
+
+Author: Vlad Nedelcu
+Date: Jul 2021
+License: MIT
+
+Copyright 2021 Vlad Nedelcu
+*/
+use std::collections::BTreeMap;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+
+/// Serves the scripts below from a single interpreter, see its docstring for
+/// the protocol.
+const WORKER_SCRIPT: &str = include_str!("scripts/worker.py");
+/// The scripts run by the Python backend. They are static, everything they
+/// work on is passed as arguments.
+const PROJECT_SCRIPT: &str = include_str!("scripts/project.py");
+const OBJECT_SCRIPT: &str = include_str!("scripts/object.py");
+/// Helpers the scripts import, loaded once by the worker.
+const COMMON_MODULE: (&str, &str) = ("jones_common", include_str!("scripts/common.py"));
+
+/// How long a single request may take before the worker is given up on. Long
+/// enough for the scan of a large project.
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// A script the worker can run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Script {
+    Project,
+    Object,
+}
+impl Script {
+    fn name(self) -> &'static str {
+        match self {
+            Script::Project => "project",
+            Script::Object => "object",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum WorkerError {
+    /// The interpreter could not be started.
+    Start(PathBuf, io::Error),
+    /// The worker exited, or closed its pipes, before answering.
+    Died,
+    /// The worker did not answer in time and was stopped.
+    TimedOut(Duration),
+    /// The script raised an exception, given with its traceback.
+    Script(String),
+    /// The worker answered something which is not a response.
+    InvalidResponse(String),
+}
+impl fmt::Display for WorkerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WorkerError::Start(python, error) => {
+                write!(f, "Could not run {}: {}", python.display(), error)
+            }
+            WorkerError::Died => write!(f, "The Python worker stopped unexpectedly"),
+            WorkerError::TimedOut(timeout) => write!(
+                f,
+                "The Python worker did not answer within {} seconds and was stopped",
+                timeout.as_secs()
+            ),
+            WorkerError::Script(traceback) => write!(f, "{}", traceback.trim_end()),
+            WorkerError::InvalidResponse(error) => {
+                write!(f, "Invalid Python worker response: {}", error)
+            }
+        }
+    }
+}
+
+/// The first line sent to a worker, with everything it serves.
+#[derive(Serialize)]
+struct Sources<'a> {
+    modules: BTreeMap<&'a str, &'a str>,
+    scripts: BTreeMap<&'a str, &'a str>,
+}
+
+#[derive(Serialize)]
+struct Request<'a> {
+    id: u64,
+    script: &'a str,
+    arguments: Vec<Argument>,
+}
+
+/// An argument is sent as a string, or as its bytes when it is a path which
+/// is not valid UTF-8.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Argument {
+    Text(String),
+    Bytes(Vec<u8>),
+}
+impl From<&OsStr> for Argument {
+    fn from(argument: &OsStr) -> Self {
+        match argument.to_str() {
+            Some(text) => Argument::Text(text.to_string()),
+            None => Argument::Bytes(os_bytes(argument)),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn os_bytes(argument: &OsStr) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    argument.as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+fn os_bytes(argument: &OsStr) -> Vec<u8> {
+    argument.to_string_lossy().into_owned().into_bytes()
+}
+
+#[derive(Deserialize)]
+struct Response {
+    id: u64,
+    #[serde(default)]
+    document: Option<Box<RawValue>>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+/// A Python interpreter kept running for all the requests of a jones run. It
+/// is started on the first request, and started again when it died.
+#[derive(Debug)]
+pub struct PythonWorker {
+    python: PathBuf,
+    timeout: Duration,
+    process: Mutex<Option<WorkerProcess>>,
+}
+impl PythonWorker {
+    pub fn new(python: PathBuf) -> Self {
+        PythonWorker::with_timeout(python, REQUEST_TIMEOUT)
+    }
+
+    pub fn with_timeout(python: PathBuf, timeout: Duration) -> Self {
+        PythonWorker {
+            python,
+            timeout,
+            process: Mutex::new(None),
+        }
+    }
+
+    /// Runs a script on the worker and returns the JSON document it made.
+    /// A worker found dead gets restarted, and the request sent once more.
+    pub fn run(&self, script: Script, arguments: &[OsString]) -> Result<Vec<u8>, WorkerError> {
+        let mut process = self
+            .process
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut restarted = false;
+        loop {
+            if process.is_none() {
+                *process = Some(WorkerProcess::start(&self.python)?);
+            }
+            let running = process.as_mut().expect("the worker was just started");
+            match running.request(script, arguments, self.timeout) {
+                Err(WorkerError::Died) if !restarted => {
+                    *process = None;
+                    restarted = true;
+                }
+                Err(error @ (WorkerError::Died | WorkerError::TimedOut(_))) => {
+                    *process = None;
+                    return Err(error);
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// A running worker, with the pipe requests are written to and the answers
+/// read from its stdout by a thread, so that waiting on them can time out.
+#[derive(Debug)]
+struct WorkerProcess {
+    child: Child,
+    requests: ChildStdin,
+    responses: Receiver<String>,
+    next_id: u64,
+}
+impl WorkerProcess {
+    fn start(python: &Path) -> Result<Self, WorkerError> {
+        let mut child = Command::new(python)
+            .arg("-c")
+            .arg(WORKER_SCRIPT)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|error| WorkerError::Start(python.to_path_buf(), error))?;
+        let mut requests = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+
+        let (sender, responses) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let sources = Sources {
+            modules: std::iter::once(COMMON_MODULE).collect(),
+            scripts: [
+                (Script::Project.name(), PROJECT_SCRIPT),
+                (Script::Object.name(), OBJECT_SCRIPT),
+            ]
+            .iter()
+            .copied()
+            .collect(),
+        };
+        let sources = serde_json::to_string(&sources).expect("the scripts serialize");
+        // a worker which cannot read its scripts dies, and is seen dead on
+        // the first request
+        let _ = writeln!(requests, "{}", sources);
+
+        Ok(WorkerProcess {
+            child,
+            requests,
+            responses,
+            next_id: 0,
+        })
+    }
+
+    fn request(
+        &mut self,
+        script: Script,
+        arguments: &[OsString],
+        timeout: Duration,
+    ) -> Result<Vec<u8>, WorkerError> {
+        self.next_id += 1;
+        let request = Request {
+            id: self.next_id,
+            script: script.name(),
+            arguments: arguments
+                .iter()
+                .map(|argument| Argument::from(argument.as_os_str()))
+                .collect(),
+        };
+        let line = serde_json::to_string(&request).expect("requests serialize");
+        writeln!(self.requests, "{}", line)
+            .and_then(|_| self.requests.flush())
+            .map_err(|_| WorkerError::Died)?;
+
+        loop {
+            let line = match self.responses.recv_timeout(timeout) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => return Err(WorkerError::TimedOut(timeout)),
+                Err(RecvTimeoutError::Disconnected) => return Err(WorkerError::Died),
+            };
+            let response: Response = serde_json::from_str(&line)
+                .map_err(|error| WorkerError::InvalidResponse(error.to_string()))?;
+            if response.id != self.next_id {
+                // the answer to an earlier request, which was given up on
+                continue;
+            }
+            return match (response.document, response.error) {
+                (_, Some(error)) => Err(WorkerError::Script(error)),
+                (Some(document), None) => Ok(document.get().as_bytes().to_vec()),
+                (None, None) => Err(WorkerError::InvalidResponse(
+                    "neither a document nor an error".to_string(),
+                )),
+            };
+        }
+    }
+}
+impl Drop for WorkerProcess {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PythonWorker, Script, WorkerError};
+    use crate::joneslib::frameworks::enums;
+    use crate::joneslib::interpreter;
+    use crate::joneslib::parser;
+    use std::ffi::OsString;
+    use std::fs;
+    use std::time::Duration;
+
+    #[test]
+    fn test_worker_serves_requests_and_restarts() {
+        let python = match interpreter::find_python(None, None, &std::env::temp_dir()) {
+            Ok(python) => python,
+            // nothing to run the worker with
+            Err(_) => return,
+        };
+        let test_file = std::env::temp_dir().join("jones_test_worker_serves_requests.py");
+        fs::write(&test_file, "class Tool:\n    def run(self): pass\n").unwrap();
+        let arguments = [
+            test_file.clone().into_os_string(),
+            OsString::from("tools"),
+            OsString::from("Tool"),
+        ];
+
+        let worker = PythonWorker::with_timeout(python, Duration::from_secs(60));
+        for _ in 0..2 {
+            let document = worker.run(Script::Object, &arguments).unwrap();
+            let document = parser::parse_object_document(&document).unwrap();
+            let class = parser::document_class(document).unwrap();
+            assert_eq!(class.name, "Tool");
+        }
+
+        let missing = [
+            OsString::from(enums::ENUM_BASES.join(",")),
+            OsString::from("/no/such/file.py"),
+        ];
+        let document = worker.run(Script::Project, &missing).unwrap();
+        let scan = parser::parse_project(&document).unwrap();
+        assert!(scan.definitions.is_empty());
+        assert_eq!(scan.skipped.len(), 1);
+        assert_eq!(scan.skipped[0].file, "/no/such/file.py");
+        let broken = [arguments[0].clone()];
+        assert!(matches!(
+            worker.run(Script::Object, &broken),
+            Err(WorkerError::Script(_))
+        ));
+
+        // a dead worker gets replaced
+        if let Some(process) = worker.process.lock().unwrap().as_mut() {
+            process.child.kill().unwrap();
+            process.child.wait().unwrap();
+        }
+        assert!(worker.run(Script::Object, &arguments).is_ok());
+
+        fs::remove_file(&test_file).unwrap();
+    }
+}
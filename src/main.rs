@@ -11,22 +11,102 @@ Copyright 2021 Vlad Nedelcu
 mod commands;
 mod joneslib;
 
+use std::process;
+
+use commands::{Command, IndexCommand};
 use joneslib::display;
-use structopt::StructOpt;
+use joneslib::index::ProjectIndex;
+use joneslib::loader::Backend;
+use joneslib::traversal::Traversal;
 
 fn main() {
-    let comms = commands::CLI::from_args();
-    if comms.grep {
-        // Search for a keyword in class name
-        match joneslib::search(&comms.path, &comms.class_name) {
-            Some(matches) => display::class_matches(matches),
-            None => display::not_found_message(),
-        }
+    let comms = commands::CLI::parse();
+    let project_path = comms.project_path();
+    let index = if comms.no_index {
+        ProjectIndex::disabled()
     } else {
-        // Generate python class
-        match joneslib::fetch_object_details(&comms.path, &comms.class_name) {
-            Some(class) => joneslib::display::output_class(&class),
-            None => display::not_found_message(),
+        ProjectIndex::open(comms.backend, project_path)
+    };
+    if let Some(Command::Index(IndexCommand::Stats { .. })) = &comms.command {
+        // only reads the index, so no interpreter is needed
+        display::index_stats(index.location(), &index.stats());
+        return;
+    }
+
+    let backend = match Backend::new(comms.backend, comms.python.clone(), project_path) {
+        Ok(backend) => backend,
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(2);
+        }
+    };
+    let traversal = match Traversal::new(&comms.include, &comms.exclude) {
+        Ok(traversal) => traversal,
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(2);
+        }
+    };
+    let class_name = comms.class_name.as_deref().unwrap_or_default();
+
+    let mut failed = false;
+    match &comms.command {
+        Some(Command::Index(IndexCommand::Rebuild { path })) => {
+            match joneslib::rebuild_index(&backend, &traversal, &index, path) {
+                Some(scan) => {
+                    save_index(&index);
+                    display::index_stats(index.location(), &index.stats());
+                    display::skipped_files(&scan.skipped);
+                    return;
+                }
+                None => failed = true,
+            }
         }
+        Some(Command::Index(IndexCommand::Stats { .. })) => unreachable!("shown above"),
+        Some(Command::Unexpected(_)) => unreachable!("refused while parsing"),
+        None if comms.grep => {
+            // Search for a keyword in class name
+            match joneslib::search(&backend, &traversal, &index, &comms.path, class_name) {
+                Some(scan) => {
+                    display::class_matches(scan.definitions);
+                    display::skipped_files(&scan.skipped);
+                    failed = comms.strict && !scan.skipped.is_empty();
+                }
+                None => display::not_found_message(),
+            }
+        }
+        None => {
+            // Generate python class, or the function with that name
+            if let Some(class) = joneslib::fetch_object_details(
+                &backend,
+                &traversal,
+                &index,
+                &comms.path,
+                class_name,
+            ) {
+                display::output_class(&class, comms.docs);
+            } else if let Some(function) = joneslib::fetch_function_details(
+                &backend,
+                &traversal,
+                &index,
+                &comms.path,
+                class_name,
+            ) {
+                display::output_function(&function, comms.docs);
+            } else {
+                display::not_found_message();
+            }
+        }
+    }
+
+    save_index(&index);
+    if failed {
+        process::exit(1);
+    }
+}
+
+fn save_index(index: &ProjectIndex) {
+    if let Err(error) = index.save() {
+        eprintln!("Could not save the index: {}", error);
     }
 }